@@ -0,0 +1,174 @@
+//! Cycle detection for deterministic simulations, where every state is fully determined by
+//! the one before it.
+
+/// Cycle of a sequence of states `x0, f(x0), f(f(x0)), ...`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cycle {
+    /// Index of the first state that is part of the cycle.
+    pub start: usize,
+    pub length: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Brent's algorithm, which usually needs fewer steps than Floyd's.
+    Brent,
+    /// Floyd's tortoise and hare algorithm.
+    Floyd,
+}
+
+impl Cycle {
+    /// Smallest index whose state equals the state at index `n`.
+    pub fn equivalent_index(&self, n: usize) -> usize {
+        if n < self.start {
+            n
+        } else {
+            self.start + (n - self.start) % self.length
+        }
+    }
+}
+
+/// Finds the cycle reached by repeatedly applying `step` to `initial`.
+pub fn find_cycle<S, F>(initial: &S, step: F, algorithm: Algorithm) -> Cycle
+where
+    S: Clone + Eq,
+    F: FnMut(&S) -> S,
+{
+    find_cycle_with(initial, step, |a, b| a == b, algorithm)
+}
+
+/// Like [`find_cycle`], but compares states by a compact fingerprint, such as a hash or a
+/// bitmask. The fingerprint must differ between any two distinct states.
+pub fn find_cycle_by_key<S, K, F, G>(initial: &S, step: F, key: G, algorithm: Algorithm) -> Cycle
+where
+    S: Clone,
+    K: Eq,
+    F: FnMut(&S) -> S,
+    G: Fn(&S) -> K,
+{
+    find_cycle_with(initial, step, |a, b| key(a) == key(b), algorithm)
+}
+
+/// State after applying `step` to `initial` `n` times, skipping over whole cycles.
+pub fn nth_state<S, F>(initial: &S, mut step: F, n: usize, algorithm: Algorithm) -> S
+where
+    S: Clone + Eq,
+    F: FnMut(&S) -> S,
+{
+    let cycle = find_cycle(initial, &mut step, algorithm);
+    advance(initial, &mut step, cycle.equivalent_index(n))
+}
+
+/// Like [`nth_state`], but detects the cycle by comparing fingerprints of the states, as in
+/// [`find_cycle_by_key`].
+pub fn nth_state_by_key<S, K, F, G>(
+    initial: &S,
+    mut step: F,
+    key: G,
+    n: usize,
+    algorithm: Algorithm,
+) -> S
+where
+    S: Clone,
+    K: Eq,
+    F: FnMut(&S) -> S,
+    G: Fn(&S) -> K,
+{
+    let cycle = find_cycle_by_key(initial, &mut step, key, algorithm);
+    advance(initial, &mut step, cycle.equivalent_index(n))
+}
+
+fn advance<S: Clone>(initial: &S, mut step: impl FnMut(&S) -> S, n: usize) -> S {
+    (0..n).fold(initial.clone(), |state, _| step(&state))
+}
+
+fn find_cycle_with<S, F, E>(initial: &S, mut step: F, same: E, algorithm: Algorithm) -> Cycle
+where
+    S: Clone,
+    F: FnMut(&S) -> S,
+    E: Fn(&S, &S) -> bool,
+{
+    let length = match algorithm {
+        Algorithm::Brent => {
+            // Search successive powers of two for the cycle length
+            let mut power = 1;
+            let mut length = 1;
+            let mut tortoise = initial.clone();
+            let mut hare = step(initial);
+            while !same(&tortoise, &hare) {
+                if power == length {
+                    tortoise = hare.clone();
+                    power *= 2;
+                    length = 0;
+                }
+                hare = step(&hare);
+                length += 1;
+            }
+            length
+        }
+        Algorithm::Floyd => {
+            // Meet somewhere inside the cycle, then walk around it once
+            let mut tortoise = step(initial);
+            let mut hare = step(&tortoise);
+            while !same(&tortoise, &hare) {
+                tortoise = step(&tortoise);
+                let midway = step(&hare);
+                hare = step(&midway);
+            }
+            let mut length = 1;
+            hare = step(&tortoise);
+            while !same(&tortoise, &hare) {
+                hare = step(&hare);
+                length += 1;
+            }
+            length
+        }
+    };
+
+    // A hare that is one cycle ahead meets the tortoise at the start of the cycle
+    let mut start = 0;
+    let mut tortoise = initial.clone();
+    let mut hare = advance(initial, &mut step, length);
+    while !same(&tortoise, &hare) {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+
+    Cycle { start, length }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(x: &u32) -> u32 {
+        (x * x + 1) % 255
+    }
+
+    #[test]
+    fn brent_and_floyd_agree() {
+        let states: Vec<u32> = (0..100)
+            .scan(3, |x, _| Some(std::mem::replace(x, step(x))))
+            .collect();
+        for algorithm in [Algorithm::Brent, Algorithm::Floyd] {
+            let cycle = find_cycle(&3, step, algorithm);
+            assert_eq!(states[cycle.start], states[cycle.start + cycle.length]);
+            assert_ne!(
+                states[cycle.start - 1],
+                states[cycle.start - 1 + cycle.length]
+            );
+            assert_eq!(find_cycle_by_key(&3, step, |&x| x, algorithm), cycle);
+            assert_eq!(nth_state(&3, step, 97, algorithm), states[97]);
+            // Every state is below 255, so a single byte identifies it
+            assert_eq!(
+                nth_state_by_key(&3, step, |&x| x as u8, 1_000_000_007, algorithm),
+                nth_state(&3, step, 1_000_000_007, algorithm)
+            );
+            assert_eq!(
+                nth_state_by_key(&3, step, |&x| x as u8, 97, algorithm),
+                states[97]
+            );
+        }
+    }
+}
@@ -1,82 +1,327 @@
-use static_init::dynamic;
-use std::collections::BTreeMap;
-use std::iter;
-
-#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
-enum Direction {
-    U,
-    L,
-    D,
-    R,
-}
-
-#[dynamic]
-static LUT: BTreeMap<(Direction, char), Direction> = [
-    ((Direction::U, '|'), Direction::U),
-    ((Direction::U, '7'), Direction::L),
-    ((Direction::U, 'F'), Direction::R),
-    ((Direction::L, '-'), Direction::L),
-    ((Direction::L, 'F'), Direction::D),
-    ((Direction::L, 'L'), Direction::U),
-    ((Direction::D, '|'), Direction::D),
-    ((Direction::D, 'L'), Direction::R),
-    ((Direction::D, 'J'), Direction::L),
-    ((Direction::R, '-'), Direction::R),
-    ((Direction::R, 'J'), Direction::U),
-    ((Direction::R, '7'), Direction::D),
-]
-.into_iter()
-.collect();
-
-fn step((y, x): (usize, usize), dir: Direction) -> Option<(usize, usize)> {
-    Some(match dir {
-        Direction::U => (y.checked_sub(1)?, x),
-        Direction::L => (y, x.checked_sub(1)?),
-        Direction::D => (y.checked_add(1)?, x),
-        Direction::R => (y, x.checked_add(1)?),
-    })
-}
+use aoc_runner_derive::{aoc, aoc_generator};
 
-fn part1_helper(maze: &[&str]) -> Option<Vec<(usize, usize)>> {
-    for (y, line) in maze.iter().enumerate() {
-        for (x, char) in line.char_indices() {
-            if char != 'S' {
-                continue;
-            }
-            let start_pos = (y, x);
-            for mut dir in [Direction::U, Direction::L, Direction::D, Direction::R] {
-                let mut pos = start_pos;
-                let mut path = iter::from_fn(|| {
-                    pos = step(pos, dir)?;
-                    dir =
-                        *LUT.get(&(dir, maze[pos.0..].iter().next()?[pos.1..].chars().next()?))?;
-                    Some(pos)
-                })
-                .collect::<Vec<_>>();
-                if pos == start_pos && !path.is_empty() {
-                    path.push(pos);
-                    return Some(path);
-                }
-            }
-        }
-    }
-    None
+#[aoc_generator(day10)]
+fn parse(input: &str) -> utils::PipeMaze {
+    input.parse().unwrap()
 }
 
 #[aoc(day10, part1)]
-pub fn part1(data: &str) -> Option<usize> {
-    Some(part1_helper(&data.lines().collect::<Vec<_>>())?.len() / 2)
+pub fn part1(maze: &utils::PipeMaze) -> usize {
+    maze.find_loop().unwrap().len() / 2
 }
 
 #[aoc(day10, part2)]
-pub fn part2(data: &str) -> Option<usize> {
-    let path = part1_helper(&data.lines().collect::<Vec<_>>())?;
+pub fn part2(maze: &utils::PipeMaze) -> usize {
+    let path = maze.find_loop().unwrap();
+    // Shoelace formula for the area, then Pick's theorem for the interior points
     let (n, m) = path
         .iter()
         .zip(path[1..].iter().chain(path.iter()))
         .map(|((y0, x0), (y1, x1))| (x0 * y1, x1 * y0))
         .fold((0, 0), |(a, b), (c, d)| (a + c, b + d));
-    Some((2 + n.max(m) - n.min(m) - path.len()) / 2)
+    (2 + n.max(m) - n.min(m) - path.len()) / 2
+}
+
+pub mod utils {
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub enum Direction {
+        U,
+        L,
+        D,
+        R,
+    }
+
+    impl Direction {
+        pub fn opposite(self) -> Self {
+            match self {
+                Self::U => Self::D,
+                Self::L => Self::R,
+                Self::D => Self::U,
+                Self::R => Self::L,
+            }
+        }
+
+        fn step(self, (y, x): (usize, usize)) -> Option<(usize, usize)> {
+            Some(match self {
+                Self::U => (y.checked_sub(1)?, x),
+                Self::L => (y, x.checked_sub(1)?),
+                Self::D => (y.checked_add(1)?, x),
+                Self::R => (y, x.checked_add(1)?),
+            })
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub enum Tile {
+        Vertical,
+        Horizontal,
+        NorthEast,
+        NorthWest,
+        SouthWest,
+        SouthEast,
+        Ground,
+        Start,
+    }
+
+    impl TryFrom<char> for Tile {
+        type Error = &'static str;
+        fn try_from(value: char) -> Result<Self, Self::Error> {
+            match value {
+                '|' => Ok(Self::Vertical),
+                '-' => Ok(Self::Horizontal),
+                'L' => Ok(Self::NorthEast),
+                'J' => Ok(Self::NorthWest),
+                '7' => Ok(Self::SouthWest),
+                'F' => Ok(Self::SouthEast),
+                '.' => Ok(Self::Ground),
+                'S' => Ok(Self::Start),
+                _ => Err("Unknown tile type"),
+            }
+        }
+    }
+
+    const PIPES: [Tile; 6] = [
+        Tile::Vertical,
+        Tile::Horizontal,
+        Tile::NorthEast,
+        Tile::NorthWest,
+        Tile::SouthWest,
+        Tile::SouthEast,
+    ];
+
+    impl Tile {
+        /// The two directions a pipe connects, if the tile is a pipe.
+        pub fn connections(self) -> Option<[Direction; 2]> {
+            match self {
+                Self::Vertical => Some([Direction::U, Direction::D]),
+                Self::Horizontal => Some([Direction::L, Direction::R]),
+                Self::NorthEast => Some([Direction::U, Direction::R]),
+                Self::NorthWest => Some([Direction::U, Direction::L]),
+                Self::SouthWest => Some([Direction::D, Direction::L]),
+                Self::SouthEast => Some([Direction::D, Direction::R]),
+                Self::Ground | Self::Start => None,
+            }
+        }
+
+        pub fn connects(self, dir: Direction) -> bool {
+            self.connections().is_some_and(|dirs| dirs.contains(&dir))
+        }
+
+        /// Direction in which a pipe is left after entering it while moving in `dir`.
+        fn exit(self, dir: Direction) -> Option<Direction> {
+            let [a, b] = self.connections()?;
+            let entry = dir.opposite();
+            if entry == a {
+                Some(b)
+            } else if entry == b {
+                Some(a)
+            } else {
+                None
+            }
+        }
+
+        /// Box-drawing character of a pipe.
+        fn box_char(self) -> char {
+            match self {
+                Self::Vertical => '│',
+                Self::Horizontal => '─',
+                Self::NorthEast => '└',
+                Self::NorthWest => '┘',
+                Self::SouthWest => '┐',
+                Self::SouthEast => '┌',
+                Self::Ground => '.',
+                Self::Start => 'S',
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum MazeError {
+        InvalidTile { line: usize, column: usize },
+        MissingStart,
+        MultipleStarts,
+        NoClosedLoop,
+    }
+
+    impl std::fmt::Display for MazeError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::InvalidTile { line, column } => {
+                    write!(f, "invalid tile at line {line}, column {column}")
+                }
+                Self::MissingStart => write!(f, "maze has no start tile"),
+                Self::MultipleStarts => write!(f, "maze has more than one start tile"),
+                Self::NoClosedLoop => write!(f, "no closed loop passes through the start tile"),
+            }
+        }
+    }
+
+    impl std::error::Error for MazeError {}
+
+    pub struct PipeMaze {
+        tiles: Vec<Vec<Tile>>,
+        start: (usize, usize),
+    }
+
+    impl std::str::FromStr for PipeMaze {
+        type Err = MazeError;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let tiles = s
+                .lines()
+                .enumerate()
+                .map(|(y, line)| {
+                    line.chars()
+                        .enumerate()
+                        .map(|(x, c)| {
+                            Tile::try_from(c).map_err(|_| MazeError::InvalidTile {
+                                line: y + 1,
+                                column: x + 1,
+                            })
+                        })
+                        .collect()
+                })
+                .collect::<Result<Vec<Vec<_>>, _>>()?;
+
+            let mut starts = tiles.iter().enumerate().flat_map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, &tile)| tile == Tile::Start)
+                    .map(move |(x, _)| (y, x))
+            });
+            let start = starts.next().ok_or(MazeError::MissingStart)?;
+            if starts.next().is_some() {
+                return Err(MazeError::MultipleStarts);
+            }
+
+            Ok(Self { tiles, start })
+        }
+    }
+
+    impl PipeMaze {
+        pub fn start(&self) -> (usize, usize) {
+            self.start
+        }
+
+        /// Tile at a position, with tiles outside of the maze treated as ground.
+        pub fn tile(&self, (y, x): (usize, usize)) -> Tile {
+            self.tiles
+                .get(y)
+                .and_then(|row| row.get(x))
+                .copied()
+                .unwrap_or(Tile::Ground)
+        }
+
+        /// Follows the pipes from the start in direction `dir` and returns the positions
+        /// visited, if they lead back into the start from a direction it could connect to.
+        fn follow(&self, mut dir: Direction) -> Option<(Vec<(usize, usize)>, Direction)> {
+            let mut path = vec![self.start];
+            let mut pos = dir.step(self.start)?;
+            while pos != self.start {
+                path.push(pos);
+                dir = self.tile(pos).exit(dir)?;
+                pos = dir.step(pos)?;
+            }
+            Some((path, dir.opposite()))
+        }
+
+        /// Walks the loop through the start tile and infers which pipe the start stands
+        /// for from the two directions the loop leaves and enters it.
+        fn walk_loop(&self) -> Result<(Vec<(usize, usize)>, Tile), MazeError> {
+            [Direction::U, Direction::L, Direction::D, Direction::R]
+                .into_iter()
+                .find_map(|dir| {
+                    let (path, entry) = self.follow(dir)?;
+                    let tile = PIPES
+                        .into_iter()
+                        .find(|pipe| pipe.connects(dir) && pipe.connects(entry))?;
+                    Some((path, tile))
+                })
+                .ok_or(MazeError::NoClosedLoop)
+        }
+
+        /// Pipe hidden under the start tile.
+        pub fn start_tile(&self) -> Result<Tile, MazeError> {
+            Ok(self.walk_loop()?.1)
+        }
+
+        /// Positions `(y, x)` of the loop in walking order, beginning at the start tile.
+        pub fn find_loop(&self) -> Result<Vec<(usize, usize)>, MazeError> {
+            Ok(self.walk_loop()?.0)
+        }
+
+        /// Classifies every tile as part of the loop, enclosed by it, or outside of it, and
+        /// returns the pipe hidden under the start tile alongside.
+        fn classify(&self) -> Result<(Vec<Vec<Region>>, Tile), MazeError> {
+            let (path, start_tile) = self.walk_loop()?;
+            let width = self.tiles.iter().map(Vec::len).max().unwrap_or_default();
+            let mut regions = vec![vec![Region::Outside; width]; self.tiles.len()];
+            for &(y, x) in &path {
+                regions[y][x] = Region::Loop;
+            }
+
+            // Scan each row, toggling between outside and inside at every loop tile that
+            // connects upwards
+            for (y, row) in regions.iter_mut().enumerate() {
+                let mut inside = false;
+                for (x, region) in row.iter_mut().enumerate() {
+                    if *region == Region::Loop {
+                        let tile = match self.tile((y, x)) {
+                            Tile::Start => start_tile,
+                            tile => tile,
+                        };
+                        inside ^= tile.connects(Direction::U);
+                    } else if inside {
+                        *region = Region::Inside;
+                    }
+                }
+            }
+            Ok((regions, start_tile))
+        }
+
+        /// Positions `(y, x)` of all tiles enclosed by the loop, in reading order.
+        pub fn enclosed_tiles(&self) -> Result<Vec<(usize, usize)>, MazeError> {
+            Ok(self
+                .classify()?
+                .0
+                .iter()
+                .enumerate()
+                .flat_map(|(y, row)| {
+                    row.iter()
+                        .enumerate()
+                        .filter(|(_, &region)| region == Region::Inside)
+                        .map(move |(x, _)| (y, x))
+                })
+                .collect())
+        }
+
+        /// Draws the loop with box-drawing characters. All other tiles, including junk
+        /// pipes, are blanked out and shown as `I` if enclosed by the loop or `O` if not.
+        pub fn render(&self) -> Result<String, MazeError> {
+            let (regions, start_tile) = self.classify()?;
+            let mut rendered = String::new();
+            for (y, row) in regions.iter().enumerate() {
+                for (x, region) in row.iter().enumerate() {
+                    rendered.push(match region {
+                        Region::Inside => 'I',
+                        Region::Outside => 'O',
+                        Region::Loop => match self.tile((y, x)) {
+                            Tile::Start => start_tile.box_char(),
+                            tile => tile.box_char(),
+                        },
+                    });
+                }
+                rendered.push('\n');
+            }
+            Ok(rendered)
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Region {
+        Loop,
+        Inside,
+        Outside,
+    }
 }
 
 #[cfg(test)]
@@ -147,15 +392,69 @@ mod tests {
 
     #[test]
     fn part1_examples() {
-        assert_eq!(Some(4), part1(EXAMPLE_1));
-        assert_eq!(Some(8), part1(EXAMPLE_2));
+        assert_eq!(4, part1(&parse(EXAMPLE_1)));
+        assert_eq!(8, part1(&parse(EXAMPLE_2)));
     }
 
     #[test]
     fn part2_examples() {
-        assert_eq!(Some(4), part2(EXAMPLE_3));
-        assert_eq!(Some(4), part2(EXAMPLE_4));
-        assert_eq!(Some(8), part2(EXAMPLE_5));
-        assert_eq!(Some(10), part2(EXAMPLE_6));
+        assert_eq!(4, part2(&parse(EXAMPLE_3)));
+        assert_eq!(4, part2(&parse(EXAMPLE_4)));
+        assert_eq!(8, part2(&parse(EXAMPLE_5)));
+        assert_eq!(10, part2(&parse(EXAMPLE_6)));
+    }
+
+    #[test]
+    fn enclosed_tiles_and_rendering() {
+        for example in [EXAMPLE_3, EXAMPLE_4, EXAMPLE_5, EXAMPLE_6] {
+            let maze = parse(example);
+            assert_eq!(maze.enclosed_tiles().unwrap().len(), part2(&maze));
+        }
+
+        let maze = parse(EXAMPLE_4);
+        assert_eq!(
+            maze.enclosed_tiles().unwrap(),
+            [(6, 2), (6, 3), (6, 6), (6, 7)]
+        );
+        assert_eq!(
+            maze.render().unwrap(),
+            indoc! {"
+                OOOOOOOOOO
+                O┌──────┐O
+                O│┌────┐│O
+                O││OOOO││O
+                O││OOOO││O
+                O│└─┐┌─┘│O
+                O│II││II│O
+                O└──┘└──┘O
+                OOOOOOOOOO
+            "}
+        );
+    }
+
+    #[test]
+    fn pipe_maze_errors() {
+        use utils::{MazeError, PipeMaze, Tile};
+
+        let maze: PipeMaze = EXAMPLE_1.parse().unwrap();
+        assert_eq!(maze.start_tile(), Ok(Tile::SouthEast));
+        assert_eq!(maze.find_loop().unwrap()[..3], [(1, 1), (2, 1), (3, 1)]);
+
+        assert_eq!(
+            ".F-7.\n.|.|.".parse::<PipeMaze>().err(),
+            Some(MazeError::MissingStart)
+        );
+        assert_eq!(
+            "S-S".parse::<PipeMaze>().err(),
+            Some(MazeError::MultipleStarts)
+        );
+        assert_eq!(
+            "S-7\n|.|\nL-.".parse::<PipeMaze>().unwrap().find_loop(),
+            Err(MazeError::NoClosedLoop)
+        );
+        assert_eq!(
+            "S-7\n|x|".parse::<PipeMaze>().err(),
+            Some(MazeError::InvalidTile { line: 2, column: 2 })
+        );
     }
 }
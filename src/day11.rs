@@ -2,96 +2,154 @@ use aoc_runner_derive::{aoc, aoc_generator};
 
 // Day 11 - Part 1
 #[aoc_generator(day11, part1)]
-fn parse_galaxies(input: &str) -> Vec<(usize, usize)> {
-    utils::parse_galaxies(input)
+fn parse_galaxies(input: &str) -> Vec<(u128, u128)> {
+    utils::parse_galaxies(input).unwrap()
 }
 
 #[aoc(day11, part1)]
-fn part1(input: &[(usize, usize)]) -> usize {
-    utils::calculate_sum_of_distances(input)
+fn part1(input: &[(u128, u128)]) -> u128 {
+    utils::calculate_sum_of_distances(input).unwrap()
 }
 
 // Day 11 - Part 2
 #[aoc_generator(day11, part2)]
-fn parse_expanded_galaxies(input: &str) -> Vec<(usize, usize)> {
-    let (expanding_rows, expanding_cols) = utils::find_expanding_dimensions(input);
-    let galaxies = utils::find_galaxies(input);
-    utils::expand_universe(&galaxies, &expanding_rows, &expanding_cols, 999_999)
+fn parse_expanded_galaxies(input: &str) -> Vec<(u128, u128)> {
+    utils::parse_expanded_galaxies(input, 999_999).unwrap()
 }
 
 #[aoc(day11, part2)]
-fn part2(input: &[(usize, usize)]) -> usize {
-    utils::calculate_sum_of_distances(input)
+fn part2(input: &[(u128, u128)]) -> u128 {
+    utils::calculate_sum_of_distances(input).unwrap()
 }
 
-mod utils {
+pub mod utils {
+    use itertools::Itertools;
     use rayon::prelude::*;
-    use std::collections::HashSet;
+    use std::collections::{BTreeMap, HashSet};
 
     #[inline]
-    pub(crate) fn parse_galaxies(input: &str) -> Vec<(usize, usize)> {
-        let (expanding_rows, expanding_cols) = find_expanding_dimensions(input);
-        let galaxies = find_galaxies(input);
-        expand_universe(&galaxies, &expanding_rows, &expanding_cols, 1)
+    pub(crate) fn parse_galaxies(input: &str) -> Result<Vec<(u128, u128)>, String> {
+        parse_expanded_galaxies(input, 1)
     }
 
-    pub fn calculate_sum_of_distances(galaxies: &[(usize, usize)]) -> usize {
-        let len = galaxies.len();
-        (0..len)
-            .into_par_iter()
-            .flat_map(move |i| {
-                (i + 1..len)
-                    .into_par_iter()
-                    .map(move |j| manhattan_distance(&galaxies[i], &galaxies[j]))
-            })
-            .sum()
+    /// Sums the Manhattan distances of all pairs of galaxies one axis at a time. With the
+    /// coordinates of an axis sorted, each one is the larger coordinate of all pairs with
+    /// the coordinates before it.
+    pub fn calculate_sum_of_distances(galaxies: &[(u128, u128)]) -> Option<u128> {
+        let xs = galaxies.iter().map(|&(x, _)| x).sorted_unstable();
+        let ys = galaxies.iter().map(|&(_, y)| y).sorted_unstable();
+        sum_of_differences(xs)?.checked_add(sum_of_differences(ys)?)
     }
 
     #[inline]
-    fn manhattan_distance(a: &(usize, usize), b: &(usize, usize)) -> usize {
-        (a.0.abs_diff(b.0)) + (a.1.abs_diff(b.1))
+    fn manhattan_distance(a: &(u128, u128), b: &(u128, u128)) -> Option<u128> {
+        a.0.abs_diff(b.0).checked_add(a.1.abs_diff(b.1))
+    }
+
+    /// Distance between two galaxies, indexed in reading order starting at 0.
+    pub fn distance(galaxies: &[(u128, u128)], a: usize, b: usize) -> Option<u128> {
+        manhattan_distance(galaxies.get(a)?, galaxies.get(b)?)
+    }
+
+    /// All pairs of galaxies with their distance, or `None` where the distance overflows.
+    fn pairs(galaxies: &[(u128, u128)]) -> impl Iterator<Item = Option<(usize, usize, u128)>> + '_ {
+        (0..galaxies.len())
+            .tuple_combinations()
+            .map(|(a, b)| Some((a, b, manhattan_distance(&galaxies[a], &galaxies[b])?)))
+    }
+
+    /// Closest pair of galaxies as `(a, b, distance)`, preferring the lowest indices on ties.
+    pub fn nearest_pair(galaxies: &[(u128, u128)]) -> Option<(usize, usize, u128)> {
+        pairs(galaxies).try_fold(None, |nearest, pair| {
+            let pair = pair?;
+            Some(match nearest {
+                Some(nearest @ (_, _, distance)) if distance <= pair.2 => Some(nearest),
+                _ => Some(pair),
+            })
+        })?
+    }
+
+    /// Most distant pair of galaxies as `(a, b, distance)`.
+    pub fn farthest_pair(galaxies: &[(u128, u128)]) -> Option<(usize, usize, u128)> {
+        if galaxies.len() < 2 {
+            return None;
+        }
+        // The Manhattan distance is the largest spread of either x + y or x - y. Measuring
+        // from the smallest coordinates keeps the keys as small as possible.
+        let x_min = galaxies.iter().map(|&(x, _)| x).min()?;
+        let (y_min, y_max) = galaxies.iter().map(|&(_, y)| y).minmax().into_option()?;
+        let extremes = |key: &dyn Fn(&(u128, u128)) -> Option<u128>| {
+            let keys = galaxies.iter().map(key).collect::<Option<Vec<_>>>()?;
+            let (min, max) = (0..galaxies.len())
+                .minmax_by_key(|&i| keys[i])
+                .into_option()?;
+            Some((min, max, keys[max] - keys[min]))
+        };
+        let sum = extremes(&|&(x, y)| (x - x_min).checked_add(y - y_min))?;
+        let difference = extremes(&|&(x, y)| (x - x_min).checked_add(y_max - y))?;
+        let (a, b, distance) = std::cmp::max_by_key(sum, difference, |&(_, _, distance)| distance);
+        Some((a.min(b), a.max(b), distance))
+    }
+
+    /// Number of galaxy pairs at each distance.
+    pub fn distance_histogram(galaxies: &[(u128, u128)]) -> Option<BTreeMap<u128, usize>> {
+        pairs(galaxies).try_fold(BTreeMap::new(), |mut histogram, pair| {
+            *histogram.entry(pair?.2).or_default() += 1;
+            Some(histogram)
+        })
+    }
+
+    fn sum_of_differences(sorted: impl Iterator<Item = u128>) -> Option<u128> {
+        let mut sorted = sorted.peekable();
+        let min = sorted.peek().copied().unwrap_or_default();
+        let mut sum: u128 = 0;
+        let mut prefix_sum: u128 = 0;
+        // Measure from the smallest coordinate, so only a sum that overflows itself fails
+        for (i, coordinate) in (0..).zip(sorted.map(|coordinate| coordinate - min)) {
+            let differences = coordinate.checked_mul(i)?.checked_sub(prefix_sum)?;
+            sum = sum.checked_add(differences)?;
+            prefix_sum = prefix_sum.checked_add(coordinate)?;
+        }
+        Some(sum)
     }
 
-    pub fn find_expanding_dimensions(input: &str) -> (HashSet<usize>, HashSet<usize>) {
-        let lines: Vec<&str> = input.lines().collect();
+    /// Finds the empty rows and columns. Every line must have the same length.
+    pub fn find_expanding_dimensions(
+        input: &str,
+    ) -> Result<(HashSet<usize>, HashSet<usize>), String> {
+        let lines: Vec<&[u8]> = input.lines().map(str::as_bytes).collect();
         let row_len = lines.first().map_or(0, |s| s.len());
+        if let Some(i) = lines.iter().position(|line| line.len() != row_len) {
+            return Err(format!(
+                "Line {} has {} columns instead of {row_len}",
+                i + 1,
+                lines[i].len()
+            ));
+        }
 
-        (0..row_len)
+        let rows = (0..lines.len())
+            .into_par_iter()
+            .filter(|&y| lines[y].iter().all(|&c| c == b'.'))
+            .collect();
+        let cols = (0..row_len)
             .into_par_iter()
-            .fold(
-                || (HashSet::new(), HashSet::new()),
-                |(mut rows, mut cols), i| {
-                    let is_row_expanding = lines[i].chars().all(|c| c == '.');
-                    let is_col_expanding =
-                        lines.iter().all(|line| line.chars().nth(i).unwrap() == '.');
-
-                    if is_row_expanding {
-                        rows.insert(i);
-                    }
-                    if is_col_expanding {
-                        cols.insert(i);
-                    }
-
-                    (rows, cols)
-                },
-            )
-            .reduce(
-                || (HashSet::new(), HashSet::new()),
-                |(mut acc_rows, mut acc_cols), (rows, cols)| {
-                    acc_rows.extend(rows);
-                    acc_cols.extend(cols);
-                    (acc_rows, acc_cols)
-                },
-            )
-    }
-
-    pub fn parse_expanded_galaxies(input: &str, expansion_rate: usize) -> Vec<(usize, usize)> {
+            .filter(|&x| lines.iter().all(|line| line[x] == b'.'))
+            .collect();
+
+        Ok((rows, cols))
+    }
+
+    pub fn parse_expanded_galaxies(
+        input: &str,
+        expansion_rate: u128,
+    ) -> Result<Vec<(u128, u128)>, String> {
         // Use find_expanding_dimensions function which returns a tuple of HashSets
-        let (expanding_rows, expanding_cols) = find_expanding_dimensions(input);
+        let (expanding_rows, expanding_cols) = find_expanding_dimensions(input)?;
         let galaxies = find_galaxies(input);
 
         // Pass the HashSets to expand_universe
         expand_universe(&galaxies, &expanding_rows, &expanding_cols, expansion_rate)
+            .ok_or_else(|| "Expanded coordinates overflow".to_string())
     }
 
     #[inline]
@@ -111,32 +169,44 @@ mod utils {
         galaxies: &[(usize, usize)],
         expanding_rows: &HashSet<usize>,
         expanding_cols: &HashSet<usize>,
-        expansion_rate: usize,
-    ) -> Vec<(usize, usize)> {
-        let x_limit = galaxies.iter().max_by_key(|(x, _)| x).unwrap().0 + 1;
-        let y_limit = galaxies.iter().max_by_key(|(_, y)| y).unwrap().1 + 1;
-
-        // Use TinyVec for efficient vector operations
-        let mut expanded_galaxies: Vec<(usize, usize)> = Vec::new();
-        let mut n_expanded_rows = 0;
-
-        for y in 0..y_limit {
-            if expanding_rows.contains(&y) {
-                n_expanded_rows += expansion_rate;
-                continue;
-            }
-            let mut n_expanded_cols = 0;
-            for x in 0..x_limit {
-                if expanding_cols.contains(&x) {
-                    n_expanded_cols += expansion_rate;
-                    continue;
-                }
-                if galaxies.contains(&(x, y)) {
-                    expanded_galaxies.push((x + n_expanded_cols, y + n_expanded_rows));
-                }
-            }
-        }
-        expanded_galaxies.to_vec()
+        expansion_rate: u128,
+    ) -> Option<Vec<(u128, u128)>> {
+        let x_limit = galaxies
+            .iter()
+            .map(|&(x, _)| x + 1)
+            .max()
+            .unwrap_or_default();
+        let y_limit = galaxies
+            .iter()
+            .map(|&(_, y)| y + 1)
+            .max()
+            .unwrap_or_default();
+        let expanded_xs = expand_axis(x_limit, expanding_cols, expansion_rate)?;
+        let expanded_ys = expand_axis(y_limit, expanding_rows, expansion_rate)?;
+
+        Some(
+            galaxies
+                .iter()
+                .map(|&(x, y)| (expanded_xs[x], expanded_ys[y]))
+                .collect(),
+        )
+    }
+
+    /// Expanded coordinate of every index below `limit`, from a running sum of the
+    /// expanding indices before it.
+    fn expand_axis(
+        limit: usize,
+        expanding: &HashSet<usize>,
+        expansion_rate: u128,
+    ) -> Option<Vec<u128>> {
+        let mut n_expanded: u128 = 0;
+        (0..limit)
+            .map(|i| {
+                let coordinate = (i as u128).checked_add(n_expanded.checked_mul(expansion_rate)?);
+                n_expanded += u128::from(expanding.contains(&i));
+                coordinate
+            })
+            .collect()
     }
 }
 
@@ -165,7 +235,78 @@ mod tests {
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&utils::parse_expanded_galaxies(SAMPLE, 9)), 1030);
-        assert_eq!(part2(&utils::parse_expanded_galaxies(SAMPLE, 99)), 8410);
+        assert_eq!(
+            part2(&utils::parse_expanded_galaxies(SAMPLE, 9).unwrap()),
+            1030
+        );
+        assert_eq!(
+            part2(&utils::parse_expanded_galaxies(SAMPLE, 99).unwrap()),
+            8410
+        );
+    }
+
+    #[test]
+    fn non_square_images() {
+        const WIDE: &str = indoc! {"
+            #...#.
+            ......
+            ..#...
+        "};
+        let (rows, cols) = utils::find_expanding_dimensions(WIDE).unwrap();
+        assert_eq!(rows, [1].into());
+        assert_eq!(cols, [1, 3, 5].into());
+        assert_eq!(part1(&parse_galaxies(WIDE)), 6 + 6 + 6);
+
+        const TALL: &str = "#.\n..\n..\n.#\n";
+        assert_eq!(part1(&parse_galaxies(TALL)), 6);
+
+        assert!(utils::find_expanding_dimensions("#..\n.#\n...").is_err());
+    }
+
+    #[test]
+    fn huge_expansion_rates() {
+        // Every row or column added per empty one lengthens the example paths by 82
+        let galaxies = utils::parse_expanded_galaxies(SAMPLE, 999_999_999_999).unwrap();
+        assert_eq!(part2(&galaxies), 82 * 999_999_999_999 + 374 - 82);
+        assert!(utils::parse_expanded_galaxies(SAMPLE, u128::MAX / 2).is_err());
+    }
+
+    #[test]
+    fn pairwise_queries() {
+        let galaxies = parse_galaxies(SAMPLE);
+        // Galaxies 5 and 9 of the puzzle description are 9 apart, galaxies 1 and 7 are 15
+        assert_eq!(utils::distance(&galaxies, 4, 8), Some(9));
+        assert_eq!(utils::distance(&galaxies, 0, 6), Some(15));
+        assert_eq!(utils::distance(&galaxies, 0, 9), None);
+        assert_eq!(utils::nearest_pair(&galaxies), Some((1, 3, 5)));
+        assert_eq!(utils::farthest_pair(&galaxies).map(|(_, _, d)| d), Some(19));
+
+        let histogram = utils::distance_histogram(&galaxies).unwrap();
+        assert_eq!(histogram.values().sum::<usize>(), 36);
+        let total: u128 = histogram.iter().map(|(&d, &n)| d * n as u128).sum();
+        assert_eq!(total, 374);
+
+        let galaxies = utils::parse_expanded_galaxies(SAMPLE, 9).unwrap();
+        assert_eq!(utils::distance(&galaxies, 4, 8), Some(9 + 2 * 8));
+
+        assert_eq!(utils::nearest_pair(&[(3, 4)]), None);
+        assert_eq!(utils::farthest_pair(&[(3, 4)]), None);
+
+        // Only the two galaxies are not separated by an expanded line
+        let galaxies =
+            utils::parse_expanded_galaxies("....\n....\n..#.\n..#.\n", u128::MAX / 4).unwrap();
+        assert_eq!(utils::distance(&galaxies, 0, 1), Some(1));
+        assert_eq!(utils::calculate_sum_of_distances(&galaxies), Some(1));
+        assert_eq!(utils::nearest_pair(&galaxies), Some((0, 1, 1)));
+        assert_eq!(utils::farthest_pair(&galaxies), Some((0, 1, 1)));
+        assert_eq!(
+            utils::distance_histogram(&galaxies),
+            Some(std::collections::BTreeMap::from([(1, 1)]))
+        );
+        let galaxies = [(0, 0), (u128::MAX, u128::MAX)];
+        assert_eq!(utils::distance(&galaxies, 0, 1), None);
+        assert_eq!(utils::nearest_pair(&galaxies), None);
+        assert_eq!(utils::farthest_pair(&galaxies), None);
+        assert_eq!(utils::distance_histogram(&galaxies), None);
     }
 }
@@ -1,4 +1,5 @@
 use aoc_runner_derive::{aoc, aoc_generator};
+use num::BigUint;
 
 #[aoc_generator(day12)]
 fn parse(input: &str) -> Vec<utils::SpringSequence> {
@@ -19,7 +20,7 @@ fn parse(input: &str) -> Vec<utils::SpringSequence> {
 }
 
 #[aoc(day12, part1)]
-fn part1(input: &[utils::SpringSequence]) -> usize {
+fn part1(input: &[utils::SpringSequence]) -> BigUint {
     input
         .iter()
         .map(utils::SpringSequence::discover_arrangements)
@@ -27,36 +28,24 @@ fn part1(input: &[utils::SpringSequence]) -> usize {
 }
 
 #[aoc(day12, part2)]
-fn part2(input: &[utils::SpringSequence]) -> usize {
+fn part2(input: &[utils::SpringSequence]) -> BigUint {
     input
         .iter()
-        .map(|sequence| {
-            // Repeat the input while joining with an unknown spring
-            const N_REPEATS: usize = 5;
-            utils::SpringSequence {
-                springs: itertools::Itertools::intersperse(
-                    [&sequence.springs; N_REPEATS].into_iter(),
-                    &vec![utils::SpringType::Unknown],
-                )
-                .flatten()
-                .copied()
-                .collect(),
-                contiguously_damaged_springs: sequence
-                    .contiguously_damaged_springs
-                    .repeat(N_REPEATS),
-            }
-        })
+        // Repeat the input while joining with an unknown spring
+        .map(|sequence| sequence.unfold(5, utils::SpringType::Unknown))
         .map(|sequence| sequence.discover_arrangements())
         .sum()
 }
 
-mod utils {
+pub mod utils {
+    use num::{BigUint, One};
+
     pub struct SpringSequence {
         pub springs: Vec<SpringType>,
         pub contiguously_damaged_springs: Vec<usize>,
     }
 
-    #[derive(Clone, Copy, PartialEq, Eq)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub enum SpringType {
         Operational,
         Damaged,
@@ -75,67 +64,127 @@ mod utils {
         }
     }
 
+    impl From<SpringType> for char {
+        fn from(value: SpringType) -> Self {
+            match value {
+                SpringType::Operational => '.',
+                SpringType::Damaged => '#',
+                SpringType::Unknown => '?',
+            }
+        }
+    }
+
+    /// Lazy depth-first enumeration of the concrete arrangements of a [`SpringSequence`].
+    pub struct Arrangements<'a> {
+        sequence: &'a SpringSequence,
+        /// Partial arrangements with the index of the next group to place.
+        stack: Vec<(Vec<SpringType>, usize)>,
+    }
+
+    impl Iterator for Arrangements<'_> {
+        type Item = Vec<SpringType>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            let springs = &self.sequence.springs;
+            let groups = &self.sequence.contiguously_damaged_springs;
+
+            while let Some((mut arrangement, group)) = self.stack.pop() {
+                let i = arrangement.len();
+                if group == groups.len() {
+                    // All groups are placed, so every remaining spring must be operational
+                    if springs[i..].contains(&SpringType::Damaged) {
+                        continue;
+                    }
+                    arrangement.resize(springs.len(), SpringType::Operational);
+                    return Some(arrangement);
+                }
+                if i == springs.len() {
+                    continue;
+                }
+
+                if springs[i] != SpringType::Damaged {
+                    let mut operational = arrangement.clone();
+                    operational.push(SpringType::Operational);
+                    self.stack.push((operational, group));
+                }
+                let end = i + groups[group];
+                if end <= springs.len()
+                    && !springs[i..end].contains(&SpringType::Operational)
+                    && springs.get(end) != Some(&SpringType::Damaged)
+                {
+                    arrangement.resize(end, SpringType::Damaged);
+                    if end < springs.len() {
+                        arrangement.push(SpringType::Operational);
+                    }
+                    self.stack.push((arrangement, group + 1));
+                }
+            }
+            None
+        }
+    }
+
     impl SpringSequence {
-        pub fn discover_arrangements(&self) -> usize {
-            let last = self.contiguously_damaged_springs.last().unwrap();
-            self.contiguously_damaged_springs
-                .iter()
-                .rev()
-                .skip(1)
-                .fold(
-                    (0..self.springs.len())
-                        .map(|i| {
-                            usize::from(
-                                !(last + i > self.springs.len()
-                                    || self.springs[i..i + last]
-                                        .iter()
-                                        .any(|&spring| spring == SpringType::Operational)
-                                    || self.springs[i + last..]
-                                        .iter()
-                                        .any(|&spring| spring == SpringType::Damaged)
-                                    || i != 0 && self.springs[i - 1] == SpringType::Damaged),
-                            )
-                        })
-                        .collect::<Vec<_>>(),
-                    |counts, run| {
-                        (0..self.springs.len())
-                            .map(|i| {
-                                if run + i >= self.springs.len()
-                                    || i != 0 && self.springs[i - 1] == SpringType::Damaged
-                                    || self.springs[i..run + i]
-                                        .iter()
-                                        .any(|&spring| spring == SpringType::Operational)
-                                    || self.springs[run + i] == SpringType::Damaged
-                                {
-                                    0
-                                } else {
-                                    counts[run + i + 1..]
-                                        .iter()
-                                        .zip(
-                                            self.springs[run + i + 1..]
-                                                .iter()
-                                                .take_while(|&&spring| {
-                                                    spring != SpringType::Damaged
-                                                })
-                                                .chain([&SpringType::Damaged]),
-                                        )
-                                        .map(|(&count, _)| count)
-                                        .sum()
-                                }
-                            })
-                            .collect()
-                    },
-                )
-                .into_iter()
-                .zip(
-                    self.springs
-                        .clone()
-                        .into_iter()
-                        .take_while(|&spring| spring != SpringType::Damaged)
-                        .chain([SpringType::Damaged]),
+        /// Every way to replace the unknown springs so that the damaged groups match,
+        /// stopping after `cap` arrangements if given.
+        pub fn arrangements(&self, cap: Option<usize>) -> std::iter::Take<Arrangements<'_>> {
+            Arrangements {
+                sequence: self,
+                stack: vec![(Vec::with_capacity(self.springs.len()), 0)],
+            }
+            .take(cap.unwrap_or(usize::MAX))
+        }
+
+        /// Repeats the springs `factor` times, joined by `separator`, along with the
+        /// damaged groups.
+        pub fn unfold(&self, factor: usize, separator: SpringType) -> Self {
+            Self {
+                springs: itertools::Itertools::intersperse(
+                    itertools::repeat_n(&self.springs, factor),
+                    &vec![separator],
                 )
-                .map(|(count, _)| count)
-                .sum()
+                .flatten()
+                .copied()
+                .collect(),
+                contiguously_damaged_springs: self.contiguously_damaged_springs.repeat(factor),
+            }
+        }
+
+        /// Counts the arrangements, memoizing the count for every suffix of the springs
+        /// and of the damaged groups.
+        pub fn discover_arrangements(&self) -> BigUint {
+            let springs = &self.springs;
+            let groups = &self.contiguously_damaged_springs;
+            let n = springs.len();
+
+            // Length of the run of possibly damaged springs starting at each position
+            let mut damageable_run = vec![0; n + 1];
+            for i in (0..n).rev() {
+                if springs[i] != SpringType::Operational {
+                    damageable_run[i] = damageable_run[i + 1] + 1;
+                }
+            }
+
+            // counts[i][g]: arrangements of springs[i..] with the groups from g onwards
+            let mut counts = vec![vec![BigUint::ZERO; groups.len() + 1]; n + 1];
+            counts[n][groups.len()] = BigUint::one();
+            for i in (0..n).rev() {
+                for g in 0..=groups.len() {
+                    let mut count = BigUint::ZERO;
+                    if springs[i] != SpringType::Damaged {
+                        count += &counts[i + 1][g];
+                    }
+                    if let Some(&len) = groups.get(g) {
+                        let end = i + len;
+                        if damageable_run[i] >= len
+                            && springs.get(end) != Some(&SpringType::Damaged)
+                        {
+                            count += &counts[(end + 1).min(n)][g + 1];
+                        }
+                    }
+                    counts[i][g] = count;
+                }
+            }
+            counts.swap_remove(0).swap_remove(0)
         }
     }
 }
@@ -156,11 +205,45 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(SAMPLE)), 21);
+        assert_eq!(part1(&parse(SAMPLE)), BigUint::from(21u32));
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(SAMPLE)), 525_152);
+        assert_eq!(part2(&parse(SAMPLE)), BigUint::from(525_152u32));
+    }
+
+    #[test]
+    fn arrangements_match_counts() {
+        for sequence in parse(SAMPLE) {
+            assert_eq!(
+                BigUint::from(sequence.arrangements(None).count()),
+                sequence.discover_arrangements()
+            );
+        }
+
+        let sequence = &parse(SAMPLE)[5];
+        let arrangements = sequence
+            .arrangements(Some(2))
+            .map(|springs| springs.into_iter().map(char::from).collect::<String>())
+            .collect::<Vec<_>>();
+        assert_eq!(arrangements, [".###.##.#...", ".###.##..#.."]);
+    }
+
+    #[test]
+    fn large_unfold_factors() {
+        let sequence = &parse(SAMPLE)[5];
+        let count = |factor| {
+            sequence
+                .unfold(factor, utils::SpringType::Unknown)
+                .discover_arrangements()
+                .to_string()
+        };
+        assert_eq!(count(1), "10");
+        assert_eq!(count(20), "221683782005310058593750");
+        assert_eq!(
+            count(50),
+            "42508100014269972460227187127657089149579405784606933593750"
+        );
     }
 }
@@ -2,78 +2,168 @@ use aoc_runner_derive::{aoc, aoc_generator};
 
 #[aoc_generator(day13)]
 fn parse(input: &str) -> Vec<utils::Pattern> {
-    input
-        .split("\n\n")
-        .map(|s| utils::Pattern::new(&s))
-        .collect()
+    input.split("\n\n").map(|s| s.parse().unwrap()).collect()
 }
 
 #[aoc(day13, part1)]
 fn part1(input: &[utils::Pattern]) -> usize {
     input
         .iter()
-        .map(|pattern| {
-            pattern.solve(|start, end| start.iter().rev().zip(end.iter()).all(|(s1, s2)| s1 == s2))
-        })
+        .map(|pattern| pattern.reflection(0).unwrap().summary())
         .sum()
 }
 
 #[aoc(day13, part2)]
 fn part2(input: &[utils::Pattern]) -> usize {
-    use rayon::prelude::*;
-
     input
         .iter()
-        .par_bridge()
-        .map(|pattern| {
-            pattern.solve(|start, end| {
-                1 == start
-                    .iter()
-                    .rev()
-                    .zip(end.iter())
-                    .flat_map(|(s1, s2)| s1.chars().zip(s2.chars()).filter(|(c1, c2)| c1 != c2))
-                    .count()
-            })
-        })
+        .map(|pattern| pattern.reflection(1).unwrap().summary())
         .sum()
 }
 
-mod utils {
-    use std::io::BufRead;
+pub mod utils {
+    /// Pattern with every row and column encoded as a bitmask of its rocks, split into
+    /// 64-bit words.
+    pub struct Pattern {
+        rows: Vec<Vec<u64>>,
+        columns: Vec<Vec<u64>>,
+    }
 
-    #[derive(derive_more::Deref)]
-    pub struct Pattern(String);
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Axis {
+        /// A horizontal line between two rows.
+        Horizontal,
+        /// A vertical line between two columns.
+        Vertical,
+    }
 
-    impl Pattern {
-        pub fn new(value: &impl ToString) -> Self {
-            Self(value.to_string())
+    /// Line of reflection with the tiles that differ from their mirror image.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Reflection {
+        pub axis: Axis,
+        /// Number of rows above or columns left of the line.
+        pub line: usize,
+        /// Positions `(row, column)` of the differing tiles above or left of the line.
+        /// Flipping them makes the reflection perfect.
+        pub smudges: Vec<(usize, usize)>,
+    }
+
+    impl std::str::FromStr for Pattern {
+        type Err = &'static str;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let width = s.lines().next().map_or(0, str::len);
+            let height = s.lines().count();
+
+            let mut rows = Vec::new();
+            let mut columns = vec![vec![0; height.div_ceil(64)]; width];
+            for (y, line) in s.lines().enumerate() {
+                if line.len() != width {
+                    return Err("Pattern rows differ in length");
+                }
+                let mut row = vec![0; width.div_ceil(64)];
+                for (x, c) in line.chars().enumerate() {
+                    match c {
+                        '#' => {
+                            row[x / 64] |= 1 << (x % 64);
+                            columns[x][y / 64] |= 1 << (y % 64);
+                        }
+                        '.' => {}
+                        _ => return Err("Unknown tile type"),
+                    }
+                }
+                rows.push(row);
+            }
+            Ok(Self { rows, columns })
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum ReflectionError {
+        NoReflection,
+        MultipleReflections(Vec<Reflection>),
+    }
+
+    impl std::fmt::Display for ReflectionError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::NoReflection => write!(f, "pattern has no line of reflection"),
+                Self::MultipleReflections(reflections) => {
+                    write!(f, "pattern has {} lines of reflection", reflections.len())
+                }
+            }
+        }
+    }
+
+    impl std::error::Error for ReflectionError {}
+
+    impl Reflection {
+        /// Number of tiles that differ from their mirror image.
+        pub fn n_smudges(&self) -> usize {
+            self.smudges.len()
         }
 
-        pub fn solve<F>(&self, predicate: F) -> usize
-        where
-            F: Fn(&[String], &[String]) -> bool,
-        {
-            100 * find_reflection(&self.horizontal_lines(), &predicate).unwrap_or_default()
-                + find_reflection(&self.vertical_lines(), &predicate).unwrap_or_default()
+        /// Rows above a horizontal line times 100, or columns left of a vertical line.
+        pub fn summary(&self) -> usize {
+            match self.axis {
+                Axis::Horizontal => 100 * self.line,
+                Axis::Vertical => self.line,
+            }
         }
+    }
 
-        fn horizontal_lines(&self) -> Vec<String> {
-            self.lines().map(std::borrow::ToOwned::to_owned).collect()
+    impl Pattern {
+        /// Every line of reflection with exactly `smudges` differing tiles.
+        pub fn find_reflections(&self, smudges: usize) -> Vec<Reflection> {
+            let horizontal =
+                find_reflections(&self.rows, smudges).map(|(line, smudges)| Reflection {
+                    axis: Axis::Horizontal,
+                    line,
+                    smudges,
+                });
+            let vertical = find_reflections(&self.columns, smudges).map(|(line, smudges)| {
+                Reflection {
+                    axis: Axis::Vertical,
+                    line,
+                    // Columns were scanned as rows, so swap the coordinates back
+                    smudges: smudges.into_iter().map(|(x, y)| (y, x)).collect(),
+                }
+            });
+            horizontal.chain(vertical).collect()
         }
 
-        fn vertical_lines(&self) -> Vec<String> {
-            (0..self.lines().next().unwrap().len())
-                .map(|i| self.lines().flat_map(|line| line[i..=i].chars()).collect())
-                .collect()
+        /// The single line of reflection with exactly `smudges` differing tiles.
+        pub fn reflection(&self, smudges: usize) -> Result<Reflection, ReflectionError> {
+            let mut reflections = self.find_reflections(smudges);
+            match reflections.len() {
+                0 => Err(ReflectionError::NoReflection),
+                1 => Ok(reflections.remove(0)),
+                _ => Err(ReflectionError::MultipleReflections(reflections)),
+            }
         }
     }
 
-    fn find_reflection<T, F>(input: &[T], predicate: F) -> Option<usize>
-    where
-        T: Eq,
-        F: Fn(&[T], &[T]) -> bool,
-    {
-        (1..input.len()).find(|&i| predicate(&input[..i], &input[i..]))
+    /// Finds the reflections between `lines` with exactly `n_smudges` smudges, given as
+    /// (line, bit) positions.
+    fn find_reflections(
+        lines: &[Vec<u64>],
+        n_smudges: usize,
+    ) -> impl Iterator<Item = (usize, Vec<(usize, usize)>)> + '_ {
+        (1..lines.len()).filter_map(move |i| {
+            let mut smudges = Vec::new();
+            for (j, b) in (0..i).rev().zip(&lines[i..]) {
+                for (w, (a, b)) in lines[j].iter().zip(b).enumerate() {
+                    let mut differences = a ^ b;
+                    while differences != 0 {
+                        smudges.push((j, 64 * w + differences.trailing_zeros() as usize));
+                        differences &= differences - 1;
+                    }
+                }
+                if smudges.len() > n_smudges {
+                    return None;
+                }
+            }
+            (smudges.len() == n_smudges).then_some((i, smudges))
+        })
     }
 }
 
@@ -109,4 +199,87 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&parse(SAMPLE)), 400);
     }
+
+    #[test]
+    fn find_reflections_with_smudges() {
+        use utils::{Axis, Reflection};
+
+        let patterns = parse(SAMPLE);
+        assert_eq!(
+            patterns[1].find_reflections(1),
+            [Reflection {
+                axis: Axis::Horizontal,
+                line: 1,
+                smudges: vec![(0, 4)]
+            },]
+        );
+        assert_eq!(
+            patterns[0].find_reflections(2),
+            [Reflection {
+                axis: Axis::Vertical,
+                line: 1,
+                smudges: vec![(0, 0), (6, 0)]
+            }]
+        );
+    }
+
+    #[test]
+    fn reflection_diagnostics() {
+        use utils::{Axis, Reflection, ReflectionError};
+
+        let patterns = parse(SAMPLE);
+        assert_eq!(
+            patterns[0].reflection(1),
+            Ok(Reflection {
+                axis: Axis::Horizontal,
+                line: 3,
+                smudges: vec![(0, 0)]
+            })
+        );
+        assert_eq!(
+            patterns[1].reflection(1),
+            Ok(Reflection {
+                axis: Axis::Horizontal,
+                line: 1,
+                smudges: vec![(0, 4)]
+            })
+        );
+        assert_eq!(
+            patterns[0].reflection(2).map(|reflection| reflection.axis),
+            Ok(Axis::Vertical)
+        );
+
+        let pattern: utils::Pattern = "#.#\n.#.\n#.#".parse().unwrap();
+        assert_eq!(pattern.reflection(0), Err(ReflectionError::NoReflection));
+        let pattern: utils::Pattern = "##\n##".parse().unwrap();
+        assert!(matches!(
+            pattern.reflection(0),
+            Err(ReflectionError::MultipleReflections(reflections)) if reflections.len() == 2
+        ));
+    }
+
+    #[test]
+    fn wide_patterns() {
+        use utils::{Axis, Reflection};
+
+        let row = format!("{}#{}", ".".repeat(69), ".".repeat(30));
+        let other_row = format!("{}#", ".".repeat(99));
+        let pattern: utils::Pattern = format!("{row}\n{row}\n{other_row}").parse().unwrap();
+        assert_eq!(
+            pattern.find_reflections(0)[0],
+            Reflection {
+                axis: Axis::Horizontal,
+                line: 1,
+                smudges: vec![]
+            }
+        );
+        assert_eq!(
+            pattern.find_reflections(1)[0],
+            Reflection {
+                axis: Axis::Vertical,
+                line: 85,
+                smudges: vec![(2, 70)]
+            }
+        );
+    }
 }
@@ -9,7 +9,7 @@ fn parse(input: &str) -> utils::Platform {
     );
     input.lines().enumerate().for_each(|(y, line)| {
         line.chars().enumerate().for_each(|(x, c)| {
-            matrix[(x, y)] = c.try_into().unwrap();
+            matrix[(y, x)] = c.try_into().unwrap();
         });
     });
     utils::Platform::new(matrix)
@@ -18,7 +18,7 @@ fn parse(input: &str) -> utils::Platform {
 #[aoc(day14, part1)]
 fn part1(input: &utils::Platform) -> usize {
     let mut input = input.clone();
-    input.slide_rocks_north();
+    input.tilt(utils::Direction::North);
     input.compute_load()
 }
 
@@ -26,38 +26,51 @@ fn part1(input: &utils::Platform) -> usize {
 fn part2(input: &utils::Platform) -> usize {
     const TARGET_N_SPINS: usize = 1_000_000_000;
 
-    let mut input = input.clone();
-    let mut visited: rustc_hash::FxHashMap<utils::Platform, usize> =
-        [(input.clone(), 0)].into_iter().collect();
-
-    // Spin until the target is reached or a loop is detected
-    for i in 1..TARGET_N_SPINS {
-        use std::collections::hash_map::Entry;
-        input.spin();
-        // Determine the frequency of the spins
-        match visited.entry(input.clone()) {
-            Entry::Vacant(entry) => {
-                entry.insert(i);
-            }
-            Entry::Occupied(entry) => {
-                // Characterize the loop
-                let loop_start = entry.get();
-                let loop_frequency = i - loop_start;
-                // Spin until the target is reached
-                let spins_until_target = (TARGET_N_SPINS - loop_start) % loop_frequency;
-                for _ in 0..spins_until_target {
-                    input.spin();
-                }
-                break;
+    let spin = |platform: &utils::Platform| {
+        let mut platform = platform.clone();
+        platform.spin();
+        platform
+    };
+    crate::cycle::nth_state(input, spin, TARGET_N_SPINS, crate::cycle::Algorithm::Brent)
+        .compute_load()
+}
+
+pub mod utils {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum Direction {
+        North,
+        West,
+        South,
+        East,
+    }
+
+    impl TryFrom<char> for Direction {
+        type Error = &'static str;
+        fn try_from(value: char) -> Result<Self, Self::Error> {
+            match value {
+                'N' => Ok(Self::North),
+                'W' => Ok(Self::West),
+                'S' => Ok(Self::South),
+                'E' => Ok(Self::East),
+                _ => Err("Unknown direction"),
             }
         }
     }
 
-    input.compute_load()
-}
+    /// Parses a comma-separated sequence of tilts such as "N,E,N,W".
+    pub fn parse_tilts(s: &str) -> Result<Vec<Direction>, &'static str> {
+        s.split(',')
+            .map(|direction| {
+                let mut chars = direction.trim().chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => c.try_into(),
+                    _ => Err("Unknown direction"),
+                }
+            })
+            .collect()
+    }
 
-mod utils {
-    #[derive(Clone, PartialEq, Eq, Hash)]
+    #[derive(Clone, Copy, PartialEq, Eq, Hash)]
     pub enum Tile {
         Empty,
         DynamicRock,
@@ -86,51 +99,60 @@ mod utils {
 
         pub fn compute_load(&self) -> usize {
             self.items()
-                .map(|((_, y), tile)| {
-                    tile.eq(&Tile::DynamicRock)
-                        .then_some(self.rows - y)
-                        .unwrap_or_default()
-                })
+                .filter(|(_, tile)| **tile == Tile::DynamicRock)
+                .map(|((y, _), _)| self.rows - y)
                 .sum()
         }
 
-        pub fn slide_rocks_north(&mut self) {
-            let mut n_rocks = 0;
-            for x in 0..self.columns {
-                for y in (0..self.rows).rev() {
-                    match self[(x, y)] {
+        /// Position of the `i`-th tile of a line, counted from the edge the rocks slide to.
+        fn position(&self, direction: Direction, line: usize, i: usize) -> (usize, usize) {
+            match direction {
+                Direction::North => (i, line),
+                Direction::West => (line, i),
+                Direction::South => (self.rows - 1 - i, line),
+                Direction::East => (line, self.columns - 1 - i),
+            }
+        }
+
+        /// Slides all rounded rocks as far as possible in the given direction.
+        pub fn tilt(&mut self, direction: Direction) {
+            let (n_lines, line_len) = match direction {
+                Direction::North | Direction::South => (self.columns, self.rows),
+                Direction::West | Direction::East => (self.rows, self.columns),
+            };
+            for line in 0..n_lines {
+                // Index at which the next rock along the line comes to rest
+                let mut free = 0;
+                for i in 0..line_len {
+                    let pos = self.position(direction, line, i);
+                    match self[pos] {
                         Tile::DynamicRock => {
-                            self[(x, y)] = Tile::Empty;
-                            n_rocks += 1;
-                        }
-                        Tile::StaticRock => {
-                            if n_rocks > 0 {
-                                let start_pos = y + 1;
-                                for y_new in start_pos..(start_pos + n_rocks) {
-                                    self[(x, y_new)] = Tile::DynamicRock;
-                                }
-                                n_rocks = 0;
-                            }
+                            self[pos] = Tile::Empty;
+                            let new_pos = self.position(direction, line, free);
+                            self[new_pos] = Tile::DynamicRock;
+                            free += 1;
                         }
+                        Tile::StaticRock => free = i + 1,
                         Tile::Empty => {}
                     }
                 }
-                if n_rocks > 0 {
-                    for y_new in 0..n_rocks {
-                        self[(x, y_new)] = Tile::DynamicRock;
-                    }
-                    n_rocks = 0;
-                }
             }
         }
 
-        pub fn spin(&mut self) {
-            // North -> West -> South -> East
-            for _ in 0..4 {
-                self.slide_rocks_north();
-                self.rotate_ccw(1);
+        pub fn tilt_sequence(&mut self, directions: &[Direction]) {
+            for &direction in directions {
+                self.tilt(direction);
             }
         }
+
+        pub fn spin(&mut self) {
+            self.tilt_sequence(&[
+                Direction::North,
+                Direction::West,
+                Direction::South,
+                Direction::East,
+            ]);
+        }
     }
 }
 
@@ -161,4 +183,28 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&parse(SAMPLE)), 64);
     }
+
+    #[test]
+    fn tilt_sequences() {
+        let mut platform = parse(SAMPLE);
+        platform.tilt_sequence(&utils::parse_tilts("N,W,S,E").unwrap());
+        let after_one_cycle = parse(indoc! {"
+            .....#....
+            ....#...O#
+            ...OO##...
+            .OO#......
+            .....OOO#.
+            .O#...O#.#
+            ....O#....
+            ......OOOO
+            #...O###..
+            #..OO#....
+        "});
+        assert!(platform == after_one_cycle);
+
+        let mut platform = parse("O.#O.\n.O..O\n");
+        platform.tilt_sequence(&utils::parse_tilts("S,E").unwrap());
+        assert!(platform == parse("..#..\n.OOOO\n"));
+        assert!(utils::parse_tilts("N,X").is_err());
+    }
 }
@@ -1,63 +1,142 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[aoc_generator(day4)]
-fn parse(input: &str) -> Vec<(Vec<i64>, Vec<i64>)> {
-    input
-        .lines()
-        .map(|line| {
-            let parts: Vec<&str> = line.split(": ").nth(1).unwrap().split(" | ").collect();
-            let winning_numbers = parts[0]
-                .split_whitespace()
-                .map(|n| n.parse().unwrap())
-                .collect();
-            let my_numbers = parts[1]
-                .split_whitespace()
-                .map(|n| n.parse().unwrap())
-                .collect();
-            (winning_numbers, my_numbers)
-        })
-        .collect()
+fn parse(input: &str) -> Vec<utils::Scratchcard> {
+    utils::parse_scratchcards(input).unwrap()
 }
 
 #[aoc(day4, part1)]
-fn part1(input: &[(Vec<i64>, Vec<i64>)]) -> i64 {
+fn part1(input: &[utils::Scratchcard]) -> Option<u64> {
     input
         .iter()
-        .map(|(winning, mine)| {
-            let mut points = 0;
-            let mut has_match = false;
-            for number in mine {
-                if winning.contains(number) {
-                    if has_match {
-                        points *= 2;
-                    } else {
-                        points = 1;
-                        has_match = true;
-                    }
-                }
-            }
-            points
-        })
-        .sum()
+        .try_fold(0u64, |sum, card| sum.checked_add(card.points()?))
 }
+
 #[aoc(day4, part2)]
-fn part2(input: &[(Vec<i64>, Vec<i64>)]) -> usize {
-    let mut total_cards = 0;
-    let mut queue: Vec<(usize, usize)> = input.iter().enumerate().map(|(i, _)| (i, 1)).collect();
-
-    while let Some((card_index, copies)) = queue.pop() {
-        total_cards += copies;
-        let (winning, mine) = &input[card_index];
-        let matches = mine.iter().filter(|n| winning.contains(n)).count();
-
-        // Queue up the next set of cards, if any
-        for next_card_index in card_index + 1..input.len().min(card_index + 1 + matches) {
-            queue.push((next_card_index, copies));
+fn part2(input: &[utils::Scratchcard]) -> usize {
+    card_copies(input).iter().sum()
+}
+
+/// Number of copies of each card (including the original) after all wins are processed.
+pub fn card_copies(input: &[utils::Scratchcard]) -> Vec<usize> {
+    let mut copies = vec![1; input.len()];
+
+    // Each card only wins copies of later cards, so a single forward pass suffices
+    for (card_index, card) in input.iter().enumerate() {
+        let won_cards = card_index + 1..input.len().min(card_index + 1 + card.matches());
+        for next_card_index in won_cards {
+            copies[next_card_index] += copies[card_index];
+        }
+    }
+
+    copies
+}
+
+pub mod utils {
+    /// Largest accepted winning number, which bounds the size of the bitset.
+    pub const MAX_WINNING_NUMBER: u64 = 1 << 16;
+
+    pub struct Scratchcard {
+        pub id: usize,
+        /// Bitset of the winning numbers, 64 numbers per word.
+        winning: Vec<u64>,
+        pub numbers: Vec<u64>,
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct ParseError {
+        pub line: usize,
+        pub message: &'static str,
+    }
+
+    impl std::fmt::Display for ParseError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+
+    impl std::error::Error for ParseError {}
+
+    pub fn parse_scratchcards(input: &str) -> Result<Vec<Scratchcard>, ParseError> {
+        input
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                line.parse().map_err(|message| ParseError {
+                    line: i + 1,
+                    message,
+                })
+            })
+            .collect()
+    }
+
+    impl std::str::FromStr for Scratchcard {
+        type Err = &'static str;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let (card, numbers) = s.split_once(':').ok_or("Missing ':' after card id")?;
+            let id = card
+                .strip_prefix("Card")
+                .and_then(|id| id.trim().parse().ok())
+                .ok_or("Invalid card id")?;
+            let (winning_numbers, numbers) =
+                numbers.split_once('|').ok_or("Missing '|' separator")?;
+            let numbers = parse_numbers(numbers)?;
+
+            let mut winning = Vec::new();
+            for number in parse_numbers(winning_numbers)? {
+                if number > MAX_WINNING_NUMBER {
+                    return Err("Winning number is too large");
+                }
+                let word = (number / 64) as usize;
+                if word >= winning.len() {
+                    winning.resize(word + 1, 0);
+                }
+                winning[word] |= 1 << (number % 64);
+            }
+
+            Ok(Self {
+                id,
+                winning,
+                numbers,
+            })
         }
     }
 
-    total_cards
+    fn parse_numbers(s: &str) -> Result<Vec<u64>, &'static str> {
+        s.split_whitespace()
+            .map(|n| n.parse().map_err(|_| "Non-numeric token"))
+            .collect()
+    }
+
+    impl Scratchcard {
+        pub fn is_winning(&self, number: u64) -> bool {
+            self.winning
+                .get((number / 64) as usize)
+                .is_some_and(|word| word & (1 << (number % 64)) != 0)
+        }
+
+        pub fn matched_numbers(&self) -> Vec<u64> {
+            self.numbers
+                .iter()
+                .copied()
+                .filter(|&n| self.is_winning(n))
+                .collect()
+        }
+
+        pub fn matches(&self) -> usize {
+            self.numbers.iter().filter(|&&n| self.is_winning(n)).count()
+        }
+
+        /// Points of the card, or `None` if they do not fit into a `u64`.
+        pub fn points(&self) -> Option<u64> {
+            match self.matches() {
+                0 => Some(0),
+                n => 1u64.checked_shl(u32::try_from(n - 1).ok()?),
+            }
+        }
+    }
 }
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -74,10 +153,36 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(SAMPLE)), 13);
+        assert_eq!(part1(&parse(SAMPLE)), Some(13));
     }
     #[test]
     fn part2_example() {
         assert_eq!(part2(&parse(SAMPLE)), 30);
     }
+    #[test]
+    fn scratchcard_model() {
+        let cards = parse(SAMPLE);
+        assert_eq!(cards[0].id, 1);
+        assert_eq!(cards[0].matched_numbers(), [83, 86, 17, 48]);
+        assert_eq!(cards[0].points(), Some(8));
+        assert_eq!(cards[4].matches(), 0);
+
+        let error = utils::parse_scratchcards("Card 1: 1 2 | 3\nCard 2: 1 2 3").err();
+        assert_eq!(error.map(|e| e.line), Some(2));
+        let error = utils::parse_scratchcards("Card 1: 1 x | 3").err();
+        assert_eq!(error.map(|e| e.message), Some("Non-numeric token"));
+        let error = utils::parse_scratchcards("Card 1: 99999999999 | 1").err();
+        assert_eq!(
+            error.map(|e| e.message),
+            Some("Winning number is too large")
+        );
+
+        let card: utils::Scratchcard = format!("Card 1: 7 | {}", "7 ".repeat(65)).parse().unwrap();
+        assert_eq!(card.matches(), 65);
+        assert_eq!(card.points(), None);
+    }
+    #[test]
+    fn card_copies_example() {
+        assert_eq!(card_copies(&parse(SAMPLE)), [1, 2, 4, 8, 14, 1]);
+    }
 }
@@ -1,18 +1,312 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
-use rayon::prelude::*;
 use std::ops::Range;
 
-mod utils {
+pub mod utils {
+    use itertools::Itertools;
+    use std::collections::{HashMap, VecDeque};
+    use std::ops::Range;
+
     pub struct Almanac {
-        pub seeds: tinyvec::ArrayVec<[u64; 20]>,
-        pub mappings: tinyvec::ArrayVec<[Vec<RangeMap>; 7]>,
+        pub seeds: Vec<u64>,
+        pub mappings: Vec<Mapping>,
+    }
+
+    /// Conversion from one category (e.g. "seed") to another (e.g. "soil").
+    pub struct Mapping {
+        pub source: String,
+        pub destination: String,
+        pub ranges: Vec<RangeMap>,
     }
 
+    #[derive(Clone, Debug, PartialEq, Eq)]
     pub struct RangeMap {
         pub from: std::ops::Range<u64>,
         pub to_start: u64,
     }
+
+    impl Almanac {
+        /// Converts a single seed through every mapping stage.
+        pub fn convert(&self, seed: u64) -> u64 {
+            self.mappings
+                .iter()
+                .fold(seed, |value, mapping| convert_value(&mapping.ranges, value))
+        }
+
+        /// Pushes whole intervals through every mapping stage, splitting them at the
+        /// boundaries of the range maps they overlap.
+        pub fn convert_ranges(&self, ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+            self.mappings.iter().fold(ranges, |ranges, mapping| {
+                convert_ranges(&mapping.ranges, ranges)
+            })
+        }
+
+        /// Composes all mapping stages into a single seed-to-location function.
+        pub fn composed(&self) -> PiecewiseMap {
+            compose(self.mappings.iter())
+        }
+
+        /// Finds the shortest chain of mappings that converts `source` into `destination`.
+        pub fn chain(&self, source: &str, destination: &str) -> Result<Vec<&Mapping>, String> {
+            // Breadth-first search over the categories, remembering how each was reached
+            let mut reached_by: HashMap<&str, Option<&Mapping>> = HashMap::new();
+            let mut queue = VecDeque::from([source]);
+            reached_by.insert(source, None);
+            while let Some(category) = queue.pop_front() {
+                if category == destination {
+                    let mut chain = Vec::new();
+                    let mut category = destination;
+                    while let Some(mapping) = reached_by[category] {
+                        chain.push(mapping);
+                        category = &mapping.source;
+                    }
+                    chain.reverse();
+                    return Ok(chain);
+                }
+                for mapping in self.mappings.iter().filter(|m| m.source == category) {
+                    if !reached_by.contains_key(mapping.destination.as_str()) {
+                        reached_by.insert(&mapping.destination, Some(mapping));
+                        queue.push_back(&mapping.destination);
+                    }
+                }
+            }
+            Err(format!(
+                "No chain of mappings converts {source} into {destination}"
+            ))
+        }
+
+        /// Composes the chain of mappings between two categories into a single function.
+        pub fn composed_between(
+            &self,
+            source: &str,
+            destination: &str,
+        ) -> Result<PiecewiseMap, String> {
+            Ok(compose(self.chain(source, destination)?.into_iter()))
+        }
+
+        /// Converts a value of category `source` into its value of category `destination`.
+        pub fn convert_between(
+            &self,
+            source: &str,
+            destination: &str,
+            value: u64,
+        ) -> Result<u64, String> {
+            Ok(self
+                .chain(source, destination)?
+                .into_iter()
+                .fold(value, |value, mapping| {
+                    convert_value(&mapping.ranges, value)
+                }))
+        }
+    }
+
+    impl RangeMap {
+        pub fn translate(&self, value: u64) -> u64 {
+            value - self.from.start + self.to_start
+        }
+    }
+
+    /// Piecewise-linear function whose pieces are sorted, non-overlapping and cover the
+    /// whole `u64` domain. Every piece translates its range by a constant offset.
+    #[derive(Clone, Debug)]
+    pub struct PiecewiseMap {
+        pieces: Vec<RangeMap>,
+    }
+
+    impl PiecewiseMap {
+        pub fn identity() -> Self {
+            Self {
+                pieces: vec![RangeMap {
+                    from: 0..u64::MAX,
+                    to_start: 0,
+                }],
+            }
+        }
+
+        /// Builds the function of a single mapping stage, filling the gaps between range
+        /// maps with identity pieces. Like [`convert_value`], overlapping range maps are
+        /// resolved in favour of the one listed first.
+        pub fn from_mapping(mapping: &[RangeMap]) -> Self {
+            // Clip every range map to the parts not covered by an earlier one
+            let mut sorted: Vec<RangeMap> = Vec::with_capacity(mapping.len());
+            for range_map in mapping {
+                let mut parts = vec![range_map.from.clone()];
+                for earlier in &sorted {
+                    parts = parts
+                        .into_iter()
+                        .flat_map(|part| {
+                            [
+                                part.start..part.end.min(earlier.from.start),
+                                part.start.max(earlier.from.end)..part.end,
+                            ]
+                        })
+                        .filter(|part| !part.is_empty())
+                        .collect();
+                }
+                sorted.extend(parts.into_iter().map(|from| RangeMap {
+                    to_start: range_map.translate(from.start),
+                    from,
+                }));
+            }
+            sorted.sort_by_key(|range_map| range_map.from.start);
+
+            let mut pieces = Vec::with_capacity(2 * sorted.len() + 1);
+            let mut cursor = 0;
+            for range_map in sorted {
+                if cursor < range_map.from.start {
+                    pieces.push(RangeMap {
+                        from: cursor..range_map.from.start,
+                        to_start: cursor,
+                    });
+                }
+                cursor = range_map.from.end;
+                pieces.push(range_map);
+            }
+            if cursor < u64::MAX {
+                pieces.push(RangeMap {
+                    from: cursor..u64::MAX,
+                    to_start: cursor,
+                });
+            }
+
+            Self { pieces }
+        }
+
+        pub fn pieces(&self) -> &[RangeMap] {
+            &self.pieces
+        }
+
+        pub fn apply(&self, value: u64) -> u64 {
+            let i = self.pieces.partition_point(|piece| piece.from.end <= value);
+            self.pieces
+                .get(i)
+                .filter(|piece| piece.from.contains(&value))
+                .map_or(value, |piece| piece.translate(value))
+        }
+
+        /// Returns the function that applies `self` first and `next` afterwards.
+        pub fn then(&self, next: &Self) -> Self {
+            let mut pieces: Vec<RangeMap> = Vec::with_capacity(self.pieces.len());
+            for piece in &self.pieces {
+                let mut start = piece.from.start;
+                while start < piece.from.end {
+                    // Split the piece wherever its image crosses a breakpoint of `next`
+                    let image = piece.translate(start);
+                    let i = next.pieces.partition_point(|p| p.from.end <= image);
+                    let (span, to_start) = match next.pieces.get(i) {
+                        Some(p) if p.from.contains(&image) => (
+                            (piece.from.end - start).min(p.from.end - image),
+                            p.translate(image),
+                        ),
+                        _ => (piece.from.end - start, image),
+                    };
+
+                    match pieces.last_mut() {
+                        Some(last)
+                            if last.from.end == start && last.translate(start) == to_start =>
+                        {
+                            last.from.end += span;
+                        }
+                        _ => pieces.push(RangeMap {
+                            from: start..start + span,
+                            to_start,
+                        }),
+                    }
+                    start += span;
+                }
+            }
+            Self { pieces }
+        }
+
+        /// Walks the breakpoints within `domain` to find the smallest value it maps to.
+        pub fn min_image(&self, domain: &[Range<u64>]) -> Option<u64> {
+            domain
+                .iter()
+                .flat_map(|range| {
+                    let i = self.pieces.partition_point(|p| p.from.end <= range.start);
+                    self.pieces[i..]
+                        .iter()
+                        .take_while(|p| p.from.start < range.end)
+                        .map(|p| p.translate(p.from.start.max(range.start)))
+                })
+                .min()
+        }
+
+        pub fn inverse(&self) -> InverseMap {
+            let mut pieces: Vec<RangeMap> = self
+                .pieces
+                .iter()
+                .map(|piece| RangeMap {
+                    from: piece.to_start..piece.translate(piece.from.end),
+                    to_start: piece.from.start,
+                })
+                .collect();
+            pieces.sort_by_key(|piece| piece.from.start);
+            InverseMap { pieces }
+        }
+    }
+
+    /// Inverse of a [`PiecewiseMap`]. Several pieces may map onto the same values, so a
+    /// single value can have any number of preimages.
+    #[derive(Clone, Debug)]
+    pub struct InverseMap {
+        pieces: Vec<RangeMap>,
+    }
+
+    impl InverseMap {
+        pub fn pieces(&self) -> &[RangeMap] {
+            &self.pieces
+        }
+
+        pub fn preimage(&self, value: u64) -> Vec<u64> {
+            self.pieces
+                .iter()
+                .take_while(|piece| piece.from.start <= value)
+                .filter(|piece| piece.from.contains(&value))
+                .map(|piece| piece.translate(value))
+                .sorted()
+                .collect()
+        }
+    }
+
+    fn compose<'a>(mappings: impl Iterator<Item = &'a Mapping>) -> PiecewiseMap {
+        mappings.fold(PiecewiseMap::identity(), |composed, mapping| {
+            composed.then(&PiecewiseMap::from_mapping(&mapping.ranges))
+        })
+    }
+
+    pub fn convert_value(mapping: &[RangeMap], value: u64) -> u64 {
+        mapping
+            .iter()
+            .find(|range_map| range_map.from.contains(&value))
+            .map_or(value, |range_map| range_map.translate(value))
+    }
+
+    pub fn convert_ranges(mapping: &[RangeMap], ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+        let mut unmapped = ranges;
+        let mut mapped = Vec::with_capacity(unmapped.len());
+
+        for range_map in mapping {
+            let mut remaining = Vec::with_capacity(unmapped.len());
+            for range in unmapped {
+                // Split the range into the parts before, inside and after the range map
+                let before = range.start..range.end.min(range_map.from.start);
+                let inside =
+                    range.start.max(range_map.from.start)..range.end.min(range_map.from.end);
+                let after = range.start.max(range_map.from.end)..range.end;
+
+                if !inside.is_empty() {
+                    mapped.push(range_map.translate(inside.start)..range_map.translate(inside.end));
+                }
+                remaining.extend([before, after].into_iter().filter(|r| !r.is_empty()));
+            }
+            unmapped = remaining;
+        }
+
+        // Values not covered by any range map keep their number
+        mapped.extend(unmapped);
+        mapped
+    }
 }
 
 #[aoc_generator(day5)]
@@ -23,7 +317,7 @@ fn parse(input: &str) -> utils::Almanac {
         .next()
         .unwrap()
         .split(':')
-        .last()
+        .next_back()
         .unwrap()
         .split_ascii_whitespace()
         .map(|s| s.parse().unwrap())
@@ -31,9 +325,14 @@ fn parse(input: &str) -> utils::Almanac {
 
     let mappings = input_sections
         .map(|section| {
-            section
-                .lines()
-                .skip(1)
+            let mut lines = section.lines();
+            let (source, destination) = lines
+                .next()
+                .unwrap()
+                .trim_end_matches(" map:")
+                .split_once("-to-")
+                .unwrap();
+            let ranges = lines
                 .map(|line| {
                     let (to_start, start, length) = line
                         .split_ascii_whitespace()
@@ -48,7 +347,12 @@ fn parse(input: &str) -> utils::Almanac {
                         to_start,
                     }
                 })
-                .collect()
+                .collect();
+            utils::Mapping {
+                source: source.to_string(),
+                destination: destination.to_string(),
+                ranges,
+            }
         })
         .collect();
 
@@ -60,74 +364,20 @@ fn part1(almanac: &utils::Almanac) -> u64 {
     almanac
         .seeds
         .iter()
-        .map(|&seed| {
-            almanac.mappings.iter().fold(seed, |value, mapping| {
-                mapping
-                    .iter()
-                    .find(|range_map| range_map.from.contains(&value))
-                    .map_or(value, |range_map| {
-                        value + range_map.to_start - range_map.from.start
-                    })
-            })
-        })
+        .map(|&seed| almanac.convert(seed))
         .min()
         .unwrap()
 }
 
 #[aoc(day5, part2)]
 fn part2(almanac: &utils::Almanac) -> u64 {
-    almanac
+    let seed_ranges = almanac
         .seeds
-        .par_chunks(8)
-        .map(|chunk| (chunk[0], chunk[0] + chunk[1]))
-        .map(|(start, end)| {
-            let seed_range = start..end;
-            let mut cache = (
-                {
-                    let mut vec = tinyvec::ArrayVec::<[usize; 7]>::new();
-                    for _ in 0..almanac.mappings.len() {
-                        vec.push(usize::MAX); // fill with usize::MAX
-                    }
-                    vec
-                },
-                {
-                    let mut vec = tinyvec::ArrayVec::<[i64; 7]>::new();
-                    for _ in 0..almanac.mappings.len() {
-                        vec.push(0); // fill with 0
-                    }
-                    vec
-                },
-            );
-
-            seed_range
-                .map(|seed| {
-                    almanac
-                        .mappings
-                        .iter()
-                        .enumerate()
-                        .fold(seed, |value, (i, mapping)| {
-                            if let Some(range_map) = mapping.get(cache.0[i]) {
-                                if range_map.from.contains(&value) {
-                                    return value.wrapping_add_signed(cache.1[i]);
-                                }
-                            }
-                            mapping
-                                .iter()
-                                .enumerate()
-                                .find(|(_, range_map)| range_map.from.contains(&value))
-                                .map_or(value, |(j, range_map)| {
-                                    cache.0[i] = j;
-                                    cache.1[i] =
-                                        range_map.to_start as i64 - range_map.from.start as i64;
-                                    value.wrapping_add_signed(cache.1[i])
-                                })
-                        })
-                })
-                .min()
-                .unwrap()
-        })
-        .min()
-        .unwrap()
+        .chunks_exact(2)
+        .map(|chunk| chunk[0]..chunk[0] + chunk[1])
+        .collect_vec();
+
+    almanac.composed().min_image(&seed_ranges).unwrap()
 }
 
 #[cfg(test)]
@@ -180,4 +430,77 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&parse(SAMPLE)), 46);
     }
+
+    #[test]
+    fn convert_ranges_splits_at_boundaries() {
+        let mapping = [utils::RangeMap {
+            from: 10..20,
+            to_start: 100,
+        }];
+        let mut ranges = utils::convert_ranges(&mapping, vec![5..15, 18..25]);
+        ranges.sort_by_key(|range| range.start);
+        assert_eq!(ranges, [5..10, 20..25, 100..105, 108..110]);
+    }
+
+    #[test]
+    fn composed_matches_stagewise_conversion() {
+        let almanac = parse(SAMPLE);
+        let composed = almanac.composed();
+        for seed in 0..120 {
+            assert_eq!(composed.apply(seed), almanac.convert(seed));
+        }
+        // Seed 82 is the only seed that lands at location 46 in the example
+        assert_eq!(composed.inverse().preimage(46), [82]);
+    }
+
+    #[test]
+    fn overlapping_range_maps() {
+        // Values 10..15 are covered by both range maps, the first one listed wins
+        let mapping = [
+            utils::RangeMap {
+                from: 10..20,
+                to_start: 100,
+            },
+            utils::RangeMap {
+                from: 5..15,
+                to_start: 200,
+            },
+            utils::RangeMap {
+                from: 0..30,
+                to_start: 300,
+            },
+        ];
+        let composed = utils::PiecewiseMap::from_mapping(&mapping);
+        for value in 0..40 {
+            assert_eq!(composed.apply(value), utils::convert_value(&mapping, value));
+        }
+        assert_eq!(composed.apply(12), 102);
+        assert_eq!(composed.apply(7), 202);
+        assert_eq!(composed.apply(25), 325);
+    }
+
+    #[test]
+    fn convert_between_named_categories() {
+        let almanac = parse(SAMPLE);
+        // Soil 81 is fertilizer 81, water 81, light 74, temperature 78 and humidity 78
+        assert_eq!(almanac.convert_between("soil", "humidity", 81), Ok(78));
+        assert_eq!(almanac.convert_between("seed", "seed", 79), Ok(79));
+        assert!(almanac.convert_between("location", "seed", 46).is_err());
+        assert!(almanac.convert_between("seed", "weather", 79).is_err());
+    }
+
+    #[test]
+    fn any_number_of_seeds_and_stages() {
+        // 200 seeds pushed through 10 stages that each shift every value by one
+        let seeds = (0..200).map(|seed| (1000 + seed).to_string()).join(" ");
+        let stages = (0..10)
+            .map(|i| format!("c{i}-to-c{} map:\n1 0 100000", i + 1))
+            .join("\n\n");
+        let almanac = parse(&format!("seeds: {seeds}\n\n{stages}\n"));
+        assert_eq!(almanac.seeds.len(), 200);
+        assert_eq!(almanac.mappings.len(), 10);
+        assert_eq!(part1(&almanac), 1010);
+        assert_eq!(part2(&almanac), 1010);
+        assert_eq!(almanac.convert_between("c3", "c10", 5), Ok(12));
+    }
 }
@@ -1,87 +1,132 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-// Define a structure to hold the race details
-#[derive(Default)]
-struct Race {
-    time: i64,
-    record_distance: i64,
-}
+use num::BigUint;
+use utils::Race;
 
-// Parse the input into a vector of Race structs
 #[aoc_generator(day6, part1)]
-fn parse_input(input: &str) -> tinyvec::ArrayVec<[Race; 4]> {
-    let mut lines = input.lines();
-
-    let times: Vec<i64> = lines
-        .next()
-        .unwrap()
-        .split(':')
-        .last()
-        .unwrap()
-        .split_whitespace()
-        .map(|num| num.parse().unwrap())
-        .collect();
-
-    let distances: Vec<i64> = lines
-        .next()
-        .unwrap()
-        .split(':')
-        .last()
-        .unwrap()
-        .split_whitespace()
-        .map(|num| num.parse().unwrap())
-        .collect();
-
-    times
-        .into_iter()
-        .zip(distances)
-        .map(|(time, record_distance)| Race {
-            time,
-            record_distance,
-        })
-        .collect()
+fn parse_input(input: &str) -> Vec<Race> {
+    utils::parse_races(input, false)
 }
+
 #[aoc_generator(day6, part2)]
-fn parse_input_single_race(input: &str) -> Race {
-    let lines: Vec<&str> = input.trim().lines().collect();
-
-    // Ensure only digits are concatenated
-    let time_str = lines[0]
-        .chars()
-        .filter(|c| c.is_numeric())
-        .collect::<String>();
-    let distance_str = lines[1]
-        .chars()
-        .filter(|c| c.is_numeric())
-        .collect::<String>();
-
-    let time = time_str.parse::<i64>().unwrap();
-    let record_distance = distance_str.parse::<i64>().unwrap();
-
-    Race {
-        time,
-        record_distance,
-    }
+fn parse_input_bad_kerning(input: &str) -> Vec<Race<BigUint>> {
+    utils::parse_races(input, true)
 }
 
 #[aoc(day6, part1)]
-fn part1(races: &[Race]) -> i64 {
+fn part1(races: &[Race]) -> u128 {
     races
         .iter()
-        .map(|race| calculate_record_breaks(race))
+        .map(|race| race.record_breaks().unwrap())
         .product()
 }
 
-fn calculate_record_breaks(race: &Race) -> i64 {
-    // Find the first hold_time where hold_time * (time - hold_time) exceeds record_distance
-    match (1..race.time).find(|j| j * (race.time - j) > race.record_distance) {
-        Some(first_record) => 1 + race.time - (2 * first_record),
-        _ => 1,
-    }
+#[aoc(day6, part2)]
+fn part2(races: &[Race<BigUint>]) -> BigUint {
+    races
+        .iter()
+        .map(|race| race.record_breaks().unwrap())
+        .product()
 }
 
-#[aoc(day6, part2)]
-fn part2(race: &Race) -> i64 {
-    calculate_record_breaks(race)
+pub mod utils {
+    use num::integer::Roots;
+    use num::{CheckedAdd, CheckedMul, CheckedSub, Integer};
+    use std::ops::RangeInclusive;
+
+    /// Parses the time and distance lines into races. With `bad_kerning` the spaces
+    /// between numbers are ignored and each line describes a single race.
+    pub fn parse_races<T>(input: &str, bad_kerning: bool) -> Vec<Race<T>>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Debug,
+    {
+        let mut lines = input.lines().map(|line| {
+            let values = line.split(':').next_back().unwrap();
+            if bad_kerning {
+                let digits: String = values.chars().filter(char::is_ascii_digit).collect();
+                vec![digits.parse().unwrap()]
+            } else {
+                values
+                    .split_whitespace()
+                    .map(|num| num.parse().unwrap())
+                    .collect()
+            }
+        });
+        let times: Vec<T> = lines.next().unwrap();
+        let distances: Vec<T> = lines.next().unwrap();
+
+        times
+            .into_iter()
+            .zip(distances)
+            .map(|(time, record_distance)| Race {
+                time,
+                record_distance,
+            })
+            .collect()
+    }
+
+    // Define a structure to hold the race details
+    pub struct Race<T = u128> {
+        pub time: T,
+        pub record_distance: T,
+    }
+
+    const OVERFLOW: &str = "Race is too long to solve without overflow";
+
+    impl<T> Race<T>
+    where
+        T: Clone + Integer + Roots + From<u8> + CheckedAdd + CheckedSub + CheckedMul,
+    {
+        fn beats_record(&self, hold_time: &T) -> Result<bool, &'static str> {
+            let Some(travel_time) = self.time.checked_sub(hold_time) else {
+                return Ok(false);
+            };
+            let distance = hold_time.checked_mul(&travel_time).ok_or(OVERFLOW)?;
+            Ok(distance > self.record_distance)
+        }
+
+        /// Shortest hold time that beats the record, solved exactly from the quadratic
+        /// `hold_time * (time - hold_time) > record_distance`.
+        pub fn first_record_break(&self) -> Result<Option<T>, &'static str> {
+            let time_squared = self.time.checked_mul(&self.time).ok_or(OVERFLOW)?;
+            let min_distance = T::from(4)
+                .checked_mul(&self.record_distance)
+                .ok_or(OVERFLOW)?;
+            let Some(discriminant) = time_squared.checked_sub(&min_distance) else {
+                return Ok(None);
+            };
+
+            // The integer square root puts the guess within one of the real root, so only
+            // the neighbouring hold times need to be checked
+            let guess = (self.time.clone() - discriminant.sqrt()) / T::from(2);
+            let mut hold_time = guess.checked_sub(&T::one()).unwrap_or_else(T::zero);
+            for _ in 0..4 {
+                if self.beats_record(&hold_time)? {
+                    return Ok(Some(hold_time));
+                }
+                hold_time = hold_time.checked_add(&T::one()).ok_or(OVERFLOW)?;
+            }
+            Ok(None)
+        }
+
+        /// All hold times that beat the record, from shortest to longest.
+        pub fn winning_hold_times(&self) -> Result<Option<RangeInclusive<T>>, &'static str> {
+            // Winning hold times are symmetric around half the race time
+            Ok(self.first_record_break()?.map(|first_record| {
+                let last_record = self.time.clone() - first_record.clone();
+                first_record..=last_record
+            }))
+        }
+
+        /// Number of hold times that beat the record.
+        pub fn record_breaks(&self) -> Result<T, &'static str> {
+            Ok(self
+                .winning_hold_times()?
+                .map_or_else(T::zero, |hold_times| {
+                    hold_times.end().clone() + T::one() - hold_times.start().clone()
+                }))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -97,6 +142,58 @@ mod tests {
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse_input_single_race(SAMPLE)), 71503);
+        assert_eq!(
+            part2(&parse_input_bad_kerning(SAMPLE)),
+            BigUint::from(71503u32)
+        );
+    }
+
+    #[test]
+    fn record_breaks_with_huge_numbers() {
+        let race = &parse_input_bad_kerning(indoc! {"
+            Time:      123456789012345678901234567890123
+            Distance:  3810394688309709187623827890641642289399735559221180688950238782
+        "})[0];
+        assert_eq!(
+            race.record_breaks().unwrap().to_string(),
+            "200000000000000000000"
+        );
+        let race = Race::<u128> {
+            time: 3,
+            record_distance: 2,
+        };
+        assert_eq!(race.record_breaks(), Ok(0));
+        assert_eq!(race.winning_hold_times(), Ok(None));
+    }
+
+    #[test]
+    fn record_breaks_overflow() {
+        let race = Race::<u128> {
+            time: 100_000_000_000_000_000_000,
+            record_distance: 1,
+        };
+        assert!(race.record_breaks().is_err());
+        let race = Race {
+            time: BigUint::from(race.time),
+            record_distance: BigUint::from(race.record_distance),
+        };
+        assert_eq!(
+            race.record_breaks(),
+            Ok(BigUint::from(99_999_999_999_999_999_999u128))
+        );
+    }
+
+    #[test]
+    fn winning_hold_times_example() {
+        let races = parse_input("Time: 7 15 30 1 2\nDistance: 9 40 200 0 0");
+        let hold_times = races
+            .iter()
+            .map(Race::winning_hold_times)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            hold_times,
+            [Some(2..=5), Some(4..=11), Some(11..=19), None, Some(1..=1)]
+        );
     }
 }
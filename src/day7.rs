@@ -1,289 +1,264 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[aoc_generator(day7, part1)]
-fn parse1(input: &str) -> Vec<utils::CardHandWithBid<utils::part1::CamelCard>> {
-    utils::parse(input)
+fn parse1(input: &str) -> Vec<utils::CardHandWithBid> {
+    utils::Rules::standard().parse(input)
 }
 
 #[aoc(day7, part1)]
-fn part1(input: &[utils::CardHandWithBid<utils::part1::CamelCard>]) -> u64 {
+fn part1(input: &[utils::CardHandWithBid]) -> u64 {
     utils::solve(input)
 }
 
 #[aoc_generator(day7, part2)]
-fn parse2(input: &str) -> Vec<utils::CardHandWithBid<utils::part2::CamelCard>> {
-    utils::parse(input)
+fn parse2(input: &str) -> Vec<utils::CardHandWithBid> {
+    utils::Rules::jokers().parse(input)
 }
 
 #[aoc(day7, part2)]
-fn part2(input: &[utils::CardHandWithBid<utils::part2::CamelCard>]) -> u64 {
+fn part2(input: &[utils::CardHandWithBid]) -> u64 {
     utils::solve(input)
 }
 
-mod utils {
+pub mod utils {
     use itertools::Itertools;
-    use strum::{EnumIter, IntoEnumIterator};
 
-    pub fn parse<T: Ord + Copy + TryFrom<char>>(input: &str) -> Vec<CardHandWithBid<T>>
-    where
-        HandType: From<[T; 5]>,
-    {
-        input
-            .lines()
-            .map(|line| {
-                let (cards, bid) = line.split_ascii_whitespace().collect_tuple().unwrap();
-                let cards: [T; 5] = cards
-                    .chars()
-                    .map(|card| {
-                        card.try_into()
-                            .unwrap_or_else(|_| panic!("Invalid card: {card}"))
-                    })
-                    .collect::<Vec<_>>()
-                    .try_into()
-                    .unwrap_or_else(|_| panic!("Invalid hand: {cards}"));
-                let hand_type = cards.into();
-                CardHandWithBid {
-                    hand: CardHand { hand_type, cards },
-                    bid: bid.parse().unwrap(),
-                }
-            })
-            .collect()
-    }
-
-    pub fn solve<T: Ord + Copy>(input: &[CardHandWithBid<T>]) -> u64 {
-        input
-            .iter()
-            .sorted_by_key(|card_hand| card_hand.hand)
-            .enumerate()
-            .map(|(i, card_hand)| u64::try_from(i + 1).unwrap() * card_hand.bid)
-            .sum()
-    }
-
-    pub struct CardHandWithBid<T: Ord> {
-        pub hand: CardHand<T>,
-        pub bid: u64,
+    /// Rules of a Camel Cards variant.
+    pub struct Rules {
+        /// Cards ordered from weakest to strongest.
+        ranking: Vec<char>,
+        /// Cards that act as whatever card makes the strongest hand type.
+        wildcards: Vec<char>,
+        hand_size: usize,
     }
 
-    #[derive(Clone, Copy, Eq)]
-    pub struct CardHand<T: Ord> {
-        pub hand_type: HandType,
-        pub cards: [T; 5],
-    }
+    impl Rules {
+        pub fn new(ranking: &str, wildcards: &str, hand_size: usize) -> Result<Self, &'static str> {
+            let ranking = ranking.chars().collect_vec();
+            let wildcards = wildcards.chars().collect_vec();
+            if ranking.len() > usize::from(u8::MAX) + 1 {
+                return Err("Too many cards in ranking");
+            }
+            if !ranking.iter().all_unique() {
+                return Err("Duplicate card in ranking");
+            }
+            if !wildcards.iter().all(|card| ranking.contains(card)) {
+                return Err("Wildcard missing from ranking");
+            }
+            if hand_size == 0 {
+                return Err("Empty hand size");
+            }
+            Ok(Self {
+                ranking,
+                wildcards,
+                hand_size,
+            })
+        }
 
-    #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-    pub enum HandType {
-        HighCard,
-        OnePair,
-        TwoPair,
-        ThreeOfKind,
-        FullHouse,
-        FourOfKind,
-        FiveOfKind,
-    }
+        pub fn standard() -> Self {
+            Self::new("23456789TJQKA", "", 5).unwrap()
+        }
 
-    impl<T: Ord> PartialEq for CardHand<T> {
-        fn eq(&self, other: &Self) -> bool {
-            self.cards.eq(&other.cards)
+        /// Jacks become jokers, which are wild but the weakest card in tie-breaks.
+        pub fn jokers() -> Self {
+            Self::new("J23456789TQKA", "J", 5).unwrap()
         }
-    }
 
-    impl<T: Ord> PartialOrd for CardHand<T> {
-        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-            Some(self.cmp(other))
+        pub fn strength(&self, card: char) -> Option<u8> {
+            let strength = self.ranking.iter().position(|&c| c == card)?;
+            u8::try_from(strength).ok()
         }
-    }
 
-    impl<T: Ord> Ord for CardHand<T> {
-        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-            match self.hand_type.cmp(&other.hand_type) {
-                std::cmp::Ordering::Equal => {
-                    for i in 0..5 {
-                        match self.cards[i].cmp(&other.cards[i]) {
-                            std::cmp::Ordering::Equal => {}
-                            other => return other,
-                        }
-                    }
-                    std::cmp::Ordering::Equal
-                }
-                other => other,
+        pub fn parse_hand(&self, cards: &str) -> Result<CardHand, &'static str> {
+            let strengths = cards
+                .chars()
+                .map(|card| self.strength(card).ok_or("Invalid card"))
+                .collect::<Result<Vec<_>, _>>()?;
+            if strengths.len() != self.hand_size {
+                return Err("Invalid hand size");
             }
+            Ok(CardHand {
+                hand_type: self.hand_type(cards),
+                cards: strengths,
+            })
         }
-    }
 
-    pub mod part1 {
-        use super::{EnumIter, HandType, IntoEnumIterator};
-
-        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, EnumIter)]
-        pub enum CamelCard {
-            Two,
-            Three,
-            Four,
-            Five,
-            Six,
-            Seven,
-            Eight,
-            Nine,
-            T,
-            J,
-            Q,
-            K,
-            A,
+        pub fn parse(&self, input: &str) -> Vec<CardHandWithBid> {
+            input
+                .lines()
+                .map(|line| {
+                    let (cards, bid) = line.split_ascii_whitespace().collect_tuple().unwrap();
+                    CardHandWithBid {
+                        hand: self
+                            .parse_hand(cards)
+                            .unwrap_or_else(|err| panic!("{err}: {cards}")),
+                        bid: bid.parse().unwrap(),
+                    }
+                })
+                .collect()
         }
 
-        impl TryFrom<char> for CamelCard {
-            type Error = &'static str;
-            fn try_from(value: char) -> Result<Self, Self::Error> {
-                match value {
-                    '2' => Ok(Self::Two),
-                    '3' => Ok(Self::Three),
-                    '4' => Ok(Self::Four),
-                    '5' => Ok(Self::Five),
-                    '6' => Ok(Self::Six),
-                    '7' => Ok(Self::Seven),
-                    '8' => Ok(Self::Eight),
-                    '9' => Ok(Self::Nine),
-                    'T' => Ok(Self::T),
-                    'J' => Ok(Self::J),
-                    'Q' => Ok(Self::Q),
-                    'K' => Ok(Self::K),
-                    'A' => Ok(Self::A),
-                    _ => Err("Invalid card"),
-                }
+        pub fn hand_type(&self, cards: &str) -> HandType {
+            let n_wildcards = cards.chars().filter(|c| self.wildcards.contains(c)).count();
+            let mut group_sizes = cards
+                .chars()
+                .filter(|c| !self.wildcards.contains(c))
+                .counts()
+                .into_values()
+                .sorted_unstable_by(|a, b| b.cmp(a))
+                .collect_vec();
+
+            // Wildcards always do best by joining the largest group
+            match group_sizes.first_mut() {
+                Some(largest) => *largest += n_wildcards,
+                None => group_sizes.push(n_wildcards),
+            }
+
+            match (
+                group_sizes[0],
+                group_sizes.get(1).copied().unwrap_or_default(),
+            ) {
+                (5.., _) => HandType::FiveOfKind,
+                (4, _) => HandType::FourOfKind,
+                (3, 2..) => HandType::FullHouse,
+                (3, _) => HandType::ThreeOfKind,
+                (2, 2..) => HandType::TwoPair,
+                (2, _) => HandType::OnePair,
+                _ => HandType::HighCard,
             }
         }
 
-        impl From<[CamelCard; 5]> for HandType {
-            fn from(value: [CamelCard; 5]) -> Self {
-                let mut hand_types_raw = arrayvec::ArrayVec::<Self, 2>::new();
-                for card in CamelCard::iter() {
-                    match value.iter().filter(|&&c| c == card).count() {
-                        2 => hand_types_raw.push(Self::OnePair),
-                        3 => hand_types_raw.push(Self::ThreeOfKind),
-                        4 => {
-                            hand_types_raw.push(Self::FourOfKind);
-                            break;
-                        }
-                        5 => {
-                            hand_types_raw.push(Self::FiveOfKind);
-                            break;
-                        }
-                        _ => {}
+        pub fn card(&self, strength: u8) -> char {
+            self.ranking[usize::from(strength)]
+        }
+
+        /// Replaces every wildcard with the card it acts as: the strongest card of the
+        /// largest group, or the strongest card overall if the hand is all wildcards.
+        pub fn resolve_wildcards(&self, cards: &str) -> String {
+            let resolved = cards
+                .chars()
+                .filter(|c| !self.wildcards.contains(c))
+                .counts()
+                .into_iter()
+                .max_by_key(|&(card, count)| (count, self.strength(card)))
+                .map_or(*self.ranking.last().unwrap(), |(card, _)| card);
+            cards
+                .chars()
+                .map(|c| {
+                    if self.wildcards.contains(&c) {
+                        resolved
+                    } else {
+                        c
                     }
-                }
-                match hand_types_raw.len() {
-                    0 => Self::HighCard,
-                    1 => hand_types_raw[0],
-                    2 => {
-                        hand_types_raw.sort();
-                        match hand_types_raw[0] {
-                            Self::OnePair => match hand_types_raw[1] {
-                                Self::OnePair => Self::TwoPair,
-                                Self::ThreeOfKind => Self::FullHouse,
-                                _ => unreachable!(),
-                            },
-                            _ => unreachable!(),
-                        }
+                })
+                .collect()
+        }
+
+        /// Ranks all hands and explains how each one contributes to the total winnings.
+        pub fn report(&self, input: &[CardHandWithBid]) -> Report {
+            let ranked_hands = input
+                .iter()
+                .sorted_by(|a, b| a.hand.cmp(&b.hand))
+                .enumerate()
+                .map(|(i, card_hand)| {
+                    let cards: String =
+                        card_hand.hand.cards.iter().map(|&s| self.card(s)).collect();
+                    let rank = i + 1;
+                    RankedHand {
+                        resolved: self.resolve_wildcards(&cards),
+                        cards,
+                        hand_type: card_hand.hand.hand_type,
+                        rank,
+                        bid: card_hand.bid,
+                        winnings: u64::try_from(rank).unwrap() * card_hand.bid,
                     }
-                    _ => unreachable!(),
-                }
-            }
+                })
+                .collect();
+            Report(ranked_hands)
         }
     }
 
-    pub mod part2 {
-        use super::{EnumIter, HandType, IntoEnumIterator};
-
-        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, EnumIter)]
-        pub enum CamelCard {
-            J,
-            Two,
-            Three,
-            Four,
-            Five,
-            Six,
-            Seven,
-            Eight,
-            Nine,
-            T,
-            Q,
-            K,
-            A,
-        }
+    pub struct RankedHand {
+        pub cards: String,
+        pub hand_type: HandType,
+        /// The cards with all wildcards replaced by the card they act as.
+        pub resolved: String,
+        pub rank: usize,
+        pub bid: u64,
+        pub winnings: u64,
+    }
 
-        impl TryFrom<char> for CamelCard {
-            type Error = &'static str;
-            fn try_from(value: char) -> Result<Self, Self::Error> {
-                match value {
-                    'J' => Ok(Self::J),
-                    '2' => Ok(Self::Two),
-                    '3' => Ok(Self::Three),
-                    '4' => Ok(Self::Four),
-                    '5' => Ok(Self::Five),
-                    '6' => Ok(Self::Six),
-                    '7' => Ok(Self::Seven),
-                    '8' => Ok(Self::Eight),
-                    '9' => Ok(Self::Nine),
-                    'T' => Ok(Self::T),
-                    'Q' => Ok(Self::Q),
-                    'K' => Ok(Self::K),
-                    'A' => Ok(Self::A),
-                    _ => Err("Invalid card"),
-                }
-            }
+    #[derive(derive_more::Deref)]
+    pub struct Report(Vec<RankedHand>);
+
+    impl Report {
+        pub fn total_winnings(&self) -> u64 {
+            self.iter().map(|hand| hand.winnings).sum()
         }
+    }
 
-        impl From<[CamelCard; 5]> for HandType {
-            fn from(value: [CamelCard; 5]) -> Self {
-                let mut hand_types_raw = arrayvec::ArrayVec::<Self, 2>::new();
-                let mut n_jokers = 0;
-                for card in CamelCard::iter() {
-                    let n_cards = value.iter().filter(|&&c| c == card).count();
-                    if card == CamelCard::J {
-                        n_jokers = n_cards;
-                    }
-                    match n_cards {
-                        2 => hand_types_raw.push(Self::OnePair),
-                        3 => hand_types_raw.push(Self::ThreeOfKind),
-                        4 => {
-                            hand_types_raw.push(Self::FourOfKind);
-                            break;
-                        }
-                        5 => {
-                            hand_types_raw.push(Self::FiveOfKind);
-                            break;
-                        }
-                        _ => {}
-                    }
-                }
-                let hand_type = match hand_types_raw.len() {
-                    0 => Self::HighCard,
-                    1 => hand_types_raw[0],
-                    2 => {
-                        hand_types_raw.sort();
-                        match hand_types_raw[0] {
-                            Self::OnePair => match hand_types_raw[1] {
-                                Self::OnePair => Self::TwoPair,
-                                Self::ThreeOfKind => Self::FullHouse,
-                                _ => unreachable!(),
-                            },
-                            _ => unreachable!(),
-                        }
-                    }
-                    _ => unreachable!(),
-                };
-                match (hand_type, n_jokers) {
-                    (Self::FourOfKind, 1 | 4) | (Self::FullHouse, 2 | 3) => Self::FiveOfKind,
-                    (Self::FullHouse, 1) | (Self::ThreeOfKind, 1 | 3) | (Self::TwoPair, 2) => {
-                        Self::FourOfKind
-                    }
-                    (Self::TwoPair, 1) => Self::FullHouse,
-                    (Self::OnePair, 1 | 2) => Self::ThreeOfKind,
-                    (Self::HighCard, 1) => Self::OnePair,
-                    (hand, _) => hand,
-                }
+    impl std::fmt::Display for Report {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let width = self.first().map_or(0, |hand| hand.cards.len()).max(8);
+            writeln!(
+                f,
+                "{:>5}  {:<width$}  {:<11}  {:<width$}  {:>6}  {:>10}",
+                "Rank", "Hand", "Type", "Resolved", "Bid", "Winnings"
+            )?;
+            for hand in self.iter() {
+                writeln!(
+                    f,
+                    "{:>5}  {:<width$}  {:<11}  {:<width$}  {:>6}  {:>10}",
+                    hand.rank,
+                    hand.cards,
+                    format!("{:?}", hand.hand_type),
+                    hand.resolved,
+                    hand.bid,
+                    hand.winnings
+                )?;
             }
+            write!(
+                f,
+                "{:>5}  {:>total_width$}",
+                "Total",
+                self.total_winnings(),
+                total_width = 2 * width + 35
+            )
         }
     }
+
+    pub fn solve(input: &[CardHandWithBid]) -> u64 {
+        input
+            .iter()
+            .sorted_by(|a, b| a.hand.cmp(&b.hand))
+            .enumerate()
+            .map(|(i, card_hand)| u64::try_from(i + 1).unwrap() * card_hand.bid)
+            .sum()
+    }
+
+    pub struct CardHandWithBid {
+        pub hand: CardHand,
+        pub bid: u64,
+    }
+
+    /// Hands compare by type first and then card by card, using the strengths
+    /// assigned by the [`Rules`] ranking.
+    #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+    pub struct CardHand {
+        pub hand_type: HandType,
+        pub cards: Vec<u8>,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    pub enum HandType {
+        HighCard,
+        OnePair,
+        TwoPair,
+        ThreeOfKind,
+        FullHouse,
+        FourOfKind,
+        FiveOfKind,
+    }
 }
 
 #[cfg(test)]
@@ -308,4 +283,43 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&parse2(SAMPLE)), 5905);
     }
+
+    #[test]
+    fn custom_rules() {
+        use utils::{HandType, Rules};
+
+        // Deuces and jokers are both wild in three-card hands
+        let rules = Rules::new("J23456789TQKA", "2J", 3).unwrap();
+        assert_eq!(rules.hand_type("2JA"), HandType::ThreeOfKind);
+        assert_eq!(rules.hand_type("JJ2"), HandType::ThreeOfKind);
+        assert_eq!(rules.hand_type("KQA"), HandType::HighCard);
+        assert_eq!(rules.hand_type("KKA"), HandType::OnePair);
+        assert!(rules.parse_hand("KKAA").is_err());
+        assert!(Rules::new("23456789", "J", 5).is_err());
+        let ranking = |n_cards| (0..n_cards).filter_map(char::from_u32).collect::<String>();
+        assert!(Rules::new(&ranking(256), "", 5).is_ok());
+        assert!(Rules::new(&ranking(257), "", 5).is_err());
+    }
+
+    #[test]
+    fn report_example() {
+        let rules = utils::Rules::jokers();
+        let report = rules.report(&rules.parse(SAMPLE));
+        let summary = report
+            .iter()
+            .map(|hand| (hand.cards.as_str(), hand.resolved.as_str(), hand.rank))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("32T3K", "32T3K", 1),
+                ("KK677", "KK677", 2),
+                ("T55J5", "T5555", 3),
+                ("QQQJA", "QQQQA", 4),
+                ("KTJJT", "KTTTT", 5),
+            ]
+        );
+        assert_eq!(report.total_winnings(), 5905);
+        assert!(report.to_string().lines().nth(5).unwrap().contains("KTJJT"));
+    }
 }
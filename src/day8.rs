@@ -1,10 +1,270 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use num_integer::lcm;
 
-mod utils {
+pub mod utils {
+    use num_integer::Integer;
+    use std::collections::HashMap;
+
+    /// Desert map with node labels interned into dense ids.
     pub struct Network {
         pub steps: Vec<usize>,
-        pub map: std::collections::HashMap<String, [String; 2]>,
+        labels: Vec<String>,
+        ids: HashMap<String, usize>,
+        successors: Vec<[usize; 2]>,
+        /// Node reached from each node after following every instruction once.
+        cycle_jumps: Vec<usize>,
+    }
+
+    /// Goal hits of a single ghost. After `offset` steps the ghost's (node, instruction)
+    /// state repeats every `length` steps, so the hits in `phases` recur forever.
+    pub struct GhostCycle {
+        pub offset: u64,
+        pub length: u64,
+        /// Hits before the cycle is entered, in increasing order.
+        pub pre_cycle_hits: Vec<u64>,
+        /// Hits within the first pass through the cycle, in increasing order.
+        pub phases: Vec<u64>,
+    }
+
+    impl Network {
+        pub fn new(steps: Vec<usize>, nodes: &[(&str, [&str; 2])]) -> Result<Self, &'static str> {
+            if steps.is_empty() {
+                return Err("No instructions");
+            }
+            let labels = nodes
+                .iter()
+                .map(|(label, _)| (*label).to_string())
+                .collect::<Vec<_>>();
+            let ids: HashMap<String, usize> = labels
+                .iter()
+                .enumerate()
+                .map(|(id, label)| (label.clone(), id))
+                .collect();
+            let successors = nodes
+                .iter()
+                .map(|(_, [left, right])| {
+                    Ok([
+                        *ids.get(*left).ok_or("Unknown node")?,
+                        *ids.get(*right).ok_or("Unknown node")?,
+                    ])
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let cycle_jumps = (0..labels.len())
+                .map(|node| {
+                    steps
+                        .iter()
+                        .fold(node, |node, &step| successors[node][step])
+                })
+                .collect();
+
+            Ok(Self {
+                steps,
+                labels,
+                ids,
+                successors,
+                cycle_jumps,
+            })
+        }
+
+        pub fn id(&self, label: &str) -> Option<usize> {
+            self.ids.get(label).copied()
+        }
+
+        pub fn label(&self, node: usize) -> &str {
+            &self.labels[node]
+        }
+
+        pub fn nodes(&self) -> std::ops::Range<usize> {
+            0..self.labels.len()
+        }
+
+        /// Node reached by following the instruction at `step` (wrapping around).
+        pub fn successor(&self, node: usize, step: u64) -> usize {
+            let instruction = (step % self.steps.len() as u64) as usize;
+            self.successors[node][self.steps[instruction]]
+        }
+
+        /// Node reached after `n_steps` steps, skipping whole instruction cycles at once.
+        pub fn node_after(&self, node: usize, n_steps: u64) -> usize {
+            let cycle_len = self.steps.len() as u64;
+            let node = (0..n_steps / cycle_len).fold(node, |node, _| self.cycle_jumps[node]);
+            (0..n_steps % cycle_len).fold(node, |node, step| self.successor(node, step))
+        }
+
+        fn starts<'a>(
+            &'a self,
+            is_start: impl Fn(&str) -> bool + 'a,
+        ) -> impl Iterator<Item = usize> + 'a {
+            self.nodes().filter(move |&node| is_start(self.label(node)))
+        }
+
+        /// Walks from `start` until a goal is reached, calling `visit` for every node on the
+        /// way, and fails once a (node, instruction) state repeats.
+        fn walk(
+            &self,
+            start: usize,
+            is_goal: impl Fn(&str) -> bool,
+            mut visit: impl FnMut(usize),
+        ) -> Result<u64, String> {
+            let mut visited = vec![false; self.labels.len() * self.steps.len()];
+            let mut node = start;
+            let mut step = 0;
+            visit(node);
+            while !is_goal(self.label(node)) {
+                let state = node * self.steps.len() + (step % self.steps.len() as u64) as usize;
+                if std::mem::replace(&mut visited[state], true) {
+                    return Err(format!("No goal is reachable from {}", self.label(start)));
+                }
+                node = self.successor(node, step);
+                step += 1;
+                visit(node);
+            }
+            Ok(step)
+        }
+
+        /// Number of steps from each start to its first goal, in node order.
+        pub fn steps_to_goal(
+            &self,
+            is_start: impl Fn(&str) -> bool,
+            is_goal: impl Fn(&str) -> bool,
+        ) -> Result<Vec<u64>, String> {
+            self.starts(is_start)
+                .map(|start| self.walk(start, &is_goal, |_| {}))
+                .collect()
+        }
+
+        /// Nodes visited from each start up to and including its first goal, in node order.
+        pub fn paths_to_goal(
+            &self,
+            is_start: impl Fn(&str) -> bool,
+            is_goal: impl Fn(&str) -> bool,
+        ) -> Result<Vec<Vec<usize>>, String> {
+            self.starts(is_start)
+                .map(|start| {
+                    let mut path = Vec::new();
+                    self.walk(start, &is_goal, |node| path.push(node))?;
+                    Ok(path)
+                })
+                .collect()
+        }
+
+        /// First step at which the ghosts leaving every start are all on a goal.
+        pub fn simultaneous_arrival(
+            &self,
+            is_start: impl Fn(&str) -> bool,
+            is_goal: impl Fn(&str) -> bool,
+        ) -> Result<Option<u64>, &'static str> {
+            let cycles = self
+                .starts(is_start)
+                .map(|start| self.ghost_cycle(start, &is_goal))
+                .collect::<Vec<_>>();
+            first_simultaneous_hit(&cycles)
+        }
+
+        pub fn ghost_cycle(&self, start: usize, is_goal: impl Fn(&str) -> bool) -> GhostCycle {
+            // First visit of every (node, instruction) state, densely indexed
+            let mut visited = vec![u64::MAX; self.labels.len() * self.steps.len()];
+            let mut hits = Vec::new();
+            let mut node = start;
+            let mut step = 0;
+
+            let offset = loop {
+                let state = node * self.steps.len() + (step % self.steps.len() as u64) as usize;
+                if visited[state] != u64::MAX {
+                    break visited[state];
+                }
+                visited[state] = step;
+                if is_goal(self.label(node)) {
+                    hits.push(step);
+                }
+                node = self.successor(node, step);
+                step += 1;
+            };
+
+            let phases = hits.split_off(hits.partition_point(|&hit| hit < offset));
+            GhostCycle {
+                offset,
+                length: step - offset,
+                pre_cycle_hits: hits,
+                phases,
+            }
+        }
+    }
+
+    impl GhostCycle {
+        pub fn is_hit(&self, step: u64) -> bool {
+            if step < self.offset {
+                self.pre_cycle_hits.binary_search(&step).is_ok()
+            } else {
+                let phase = self.offset + (step - self.offset) % self.length;
+                self.phases.binary_search(&phase).is_ok()
+            }
+        }
+    }
+
+    const OVERFLOW: &str = "Simultaneous arrival overflows";
+
+    /// Finds the first step at which every ghost is on a goal at the same time.
+    pub fn first_simultaneous_hit(cycles: &[GhostCycle]) -> Result<Option<u64>, &'static str> {
+        // Before all ghosts are inside their cycles, check each step directly
+        let Some(periodic_from) = cycles.iter().map(|cycle| cycle.offset).max() else {
+            return Ok(None);
+        };
+        if let Some(step) =
+            (0..periodic_from).find(|&step| cycles.iter().all(|cycle| cycle.is_hit(step)))
+        {
+            return Ok(Some(step));
+        }
+
+        // Afterwards, combine the phases of all ghosts with the Chinese remainder theorem
+        let mut congruences = vec![(0, 1)];
+        for cycle in cycles {
+            let length = i128::from(cycle.length);
+            let mut merged = Vec::new();
+            for &congruence in &congruences {
+                for &phase in &cycle.phases {
+                    let phase = (i128::from(phase) % length, length);
+                    merged.extend(combine_congruences(congruence, phase)?);
+                }
+            }
+            // Different combinations of phases often merge into the same congruence
+            merged.sort_unstable();
+            merged.dedup();
+            congruences = merged;
+        }
+
+        let steps = congruences
+            .iter()
+            .map(|&(residue, modulus)| {
+                let periodic_from = i128::from(periodic_from);
+                let n_periods = ((periodic_from - residue).max(0) + modulus - 1) / modulus;
+                let step = n_periods.checked_mul(modulus)?.checked_add(residue)?;
+                u64::try_from(step).ok()
+            })
+            .collect::<Vec<_>>();
+        match steps.iter().flatten().min() {
+            Some(&step) => Ok(Some(step)),
+            None if steps.is_empty() => Ok(None),
+            None => Err(OVERFLOW),
+        }
+    }
+
+    /// Merges `t ≡ a (mod m)` and `t ≡ b (mod n)` into a single congruence, if compatible.
+    fn combine_congruences(
+        (a, m): (i128, i128),
+        (b, n): (i128, i128),
+    ) -> Result<Option<(i128, i128)>, &'static str> {
+        let gcd = m.extended_gcd(&n);
+        if (b - a) % gcd.gcd != 0 {
+            return Ok(None);
+        }
+        let lcm = (m / gcd.gcd).checked_mul(n).ok_or(OVERFLOW)?;
+        let k = ((b - a) / gcd.gcd)
+            .checked_mul(gcd.x)
+            .ok_or(OVERFLOW)?
+            .rem_euclid(n / gcd.gcd);
+        // Both terms are below `lcm`, so only their product can overflow
+        let residue = m.checked_mul(k).ok_or(OVERFLOW)? + a;
+        Ok(Some((residue.rem_euclid(lcm), lcm)))
     }
 }
 
@@ -23,93 +283,36 @@ fn parse(input: &str) -> utils::Network {
             _ => panic!("Invalid input"),
         })
         .collect();
-    let mut map = std::collections::HashMap::new();
-    for line in lines.skip(1) {
-        let (key, steps) = line.split('=').collect_tuple().unwrap();
-        let key = key.trim().to_string();
-        let (left, right) = steps
-            .split(',')
-            .map(|s| {
-                s.chars()
-                    .filter(|c| c.is_alphanumeric())
-                    .collect::<String>()
-            })
-            .collect_tuple()
-            .unwrap();
-        map.insert(key, [left, right]);
-    }
+    let nodes = lines
+        .skip(1)
+        .map(|line| {
+            let (key, steps) = line.split('=').collect_tuple().unwrap();
+            let (left, right) = steps
+                .split(',')
+                .map(|s| s.trim_matches(|c: char| !c.is_alphanumeric()))
+                .collect_tuple()
+                .unwrap();
+            (key.trim(), [left, right])
+        })
+        .collect_vec();
 
-    utils::Network { steps, map }
+    utils::Network::new(steps, &nodes).unwrap()
 }
 
 #[aoc(day8, part1)]
 fn part1(network: &utils::Network) -> u64 {
-    let mut current_node = "AAA".to_string();
-    let mut count = 0;
-
-    for step in network.steps.iter().cycle() {
-        count += 1;
-        current_node = network.map[&current_node][*step].clone();
-        if current_node == "ZZZ" {
-            break;
-        }
-    }
-
-    count
+    network
+        .steps_to_goal(|label| label == "AAA", |label| label == "ZZZ")
+        .unwrap()[0]
 }
 
 #[aoc(day8, part2)]
-fn part2(network: &utils::Network) -> u64 {
-    use part2_utils::Path;
-
-    let mut paths: Vec<Path> = network
-        .map
-        .keys()
-        .filter(|k| k.ends_with('A'))
-        .map(|k| Path::new(k))
-        .collect();
-
-    let mut step_counter = 0;
-    let mut paths_in_progress: Vec<usize> = (0..paths.len()).collect();
-
-    while !paths_in_progress.is_empty() {
-        for &path_index in &paths_in_progress.clone() {
-            let path = &mut paths[path_index];
-            path.current_node = network.map[&path.current_node]
-                [network.steps[step_counter % network.steps.len()]]
-            .clone();
-            if path.current_node.ends_with('Z') {
-                path.steps_taken = (step_counter + 1) as u64;
-                paths_in_progress.retain(|&i| i != path_index);
-            }
-        }
-        step_counter += 1;
-    }
-
-    paths.iter().fold(0, |acc, path| {
-        if acc == 0 {
-            path.steps_taken
-        } else {
-            lcm(acc, path.steps_taken)
-        }
-    })
+fn part2(network: &utils::Network) -> Option<u64> {
+    network
+        .simultaneous_arrival(|label| label.ends_with('A'), |label| label.ends_with('Z'))
+        .unwrap()
 }
 
-mod part2_utils {
-    pub struct Path {
-        pub current_node: String,
-        pub steps_taken: u64,
-    }
-
-    impl Path {
-        pub fn new(node: &String) -> Self {
-            Self {
-                current_node: node.clone(),
-                steps_taken: 0,
-            }
-        }
-    }
-}
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,6 +328,41 @@ mod tests {
             ZZZ = (ZZZ, ZZZ)
         "};
         assert_eq!(part1(&parse(SAMPLE)), 6);
+
+        let network = parse(SAMPLE);
+        let start = network.id("AAA").unwrap();
+        assert_eq!(network.label(network.node_after(start, 6)), "ZZZ");
+        assert_eq!(network.label(network.node_after(start, 5)), "BBB");
+
+        let paths = network
+            .paths_to_goal(|label| label == "BBB", |label| label == "ZZZ")
+            .unwrap();
+        let labels = paths[0]
+            .iter()
+            .map(|&node| network.label(node))
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["BBB", "AAA", "BBB", "ZZZ"]);
+    }
+
+    #[test]
+    fn unreachable_goal() {
+        const SAMPLE: &str = indoc! {"
+            LR
+
+            AAA = (BBB, BBB)
+            BBB = (AAA, AAA)
+            ZZZ = (ZZZ, ZZZ)
+        "};
+        let network = parse(SAMPLE);
+        assert!(network
+            .steps_to_goal(|label| label == "AAA", |label| label == "ZZZ")
+            .is_err());
+        assert_eq!(
+            network.steps_to_goal(|label| label == "ZZZ", |label| label == "ZZZ"),
+            Ok(vec![0])
+        );
+
+        assert!(utils::Network::new(vec![], &[("AAA", ["AAA", "AAA"])]).is_err());
     }
 
     #[test]
@@ -141,6 +379,71 @@ mod tests {
             22Z = (22B, 22B)
             XXX = (XXX, XXX)
         "};
-        assert_eq!(part2(&parse(SAMPLE)), 6);
+        assert_eq!(part2(&parse(SAMPLE)), Some(6));
+    }
+
+    #[test]
+    fn part2_misaligned_cycles() {
+        // The first hits (2 and 1) suggest an LCM of 2, but 4 is the first shared hit
+        const SAMPLE: &str = indoc! {"
+            L
+
+            11A = (11B, 11B)
+            11B = (11Z, 11Z)
+            11Z = (11B, 11B)
+            22A = (22Z, 22Z)
+            22Z = (22B, 22B)
+            22B = (22C, 22C)
+            22C = (22Z, 22Z)
+        "};
+        assert_eq!(part2(&parse(SAMPLE)), Some(4));
+
+        // Ghosts on even and odd steps never arrive at the same time
+        const DISJOINT: &str = indoc! {"
+            L
+
+            11A = (11B, 11B)
+            11B = (11Z, 11Z)
+            11Z = (11B, 11B)
+            22A = (22Z, 22Z)
+            22Z = (22B, 22B)
+            22B = (22Z, 22Z)
+        "};
+        assert_eq!(part2(&parse(DISJOINT)), None);
+    }
+
+    #[test]
+    fn many_phases() {
+        // Every ghost is on a goal at every step, except for the last one
+        let mut cycles = (0..40)
+            .map(|_| utils::GhostCycle {
+                offset: 0,
+                length: 2,
+                pre_cycle_hits: vec![],
+                phases: vec![0, 1],
+            })
+            .collect::<Vec<_>>();
+        cycles.push(utils::GhostCycle {
+            offset: 1,
+            length: 3,
+            pre_cycle_hits: vec![],
+            phases: vec![2],
+        });
+        assert_eq!(utils::first_simultaneous_hit(&cycles), Ok(Some(2)));
+    }
+
+    #[test]
+    fn simultaneous_hit_overflow() {
+        // Ghosts with coprime cycle lengths that only hit their goal at the end of a cycle
+        let cycles = [1u64 << 40, 3u64.pow(25), 5u64.pow(17), 7u64.pow(14)].map(|length| {
+            utils::GhostCycle {
+                offset: 0,
+                length,
+                pre_cycle_hits: vec![],
+                phases: vec![length - 1],
+            }
+        });
+        assert!(utils::first_simultaneous_hit(&cycles[..2]).is_err());
+        assert!(utils::first_simultaneous_hit(&cycles).is_err());
     }
 }
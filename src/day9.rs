@@ -4,43 +4,105 @@ use aoc_runner_derive::{aoc, aoc_generator};
 
 // Generator function to parse input data
 #[aoc_generator(day9)]
-fn parse_input(input: &str) -> Vec<Vec<i32>> {
+fn parse_input(input: &str) -> Vec<utils::Polynomial> {
     input
         .lines()
         .map(|line| {
-            line.split_whitespace()
-                .filter_map(|num| num.parse::<i32>().ok())
-                .collect()
+            let sequence: Vec<i128> = line
+                .split_whitespace()
+                .filter_map(|num| num.parse().ok())
+                .collect();
+            utils::Polynomial::fit(&sequence).unwrap()
         })
         .collect()
 }
 
-// Part 1: Calculate a specific sum based on input data
+// Part 1: Sum of the values following each sequence
 #[aoc(day9, part1)]
-fn solve_part1(input: &[Vec<i32>]) -> i32 {
-    input.iter().map(|seq| predict_next_recursive(seq)).sum()
+fn solve_part1(input: &[utils::Polynomial]) -> i128 {
+    input
+        .iter()
+        .map(|polynomial| {
+            polynomial
+                .value_at(polynomial.sequence_len() as i128)
+                .unwrap()
+        })
+        .sum()
 }
 
-// Part 2: Modification of input data and calculation
+// Part 2: Sum of the values preceding each sequence
 #[aoc(day9, part2)]
-fn solve_part2(input: &[Vec<i32>]) -> i32 {
+fn solve_part2(input: &[utils::Polynomial]) -> i128 {
     input
         .iter()
-        .map(|arr| {
-            let mut reversed = arr.clone();
-            reversed.reverse();
-            predict_next_recursive(&reversed)
-        })
+        .map(|polynomial| polynomial.value_at(-1).unwrap())
         .sum()
 }
 
-// Utility function to recursively predict the next value
-fn predict_next_recursive(row: &[i32]) -> i32 {
-    let next_row: Vec<i32> = row.windows(2).map(|w| w[1] - w[0]).collect();
-    row.last().unwrap_or(&0)
-        + next_row
-            .first()
-            .map_or(0, |_| predict_next_recursive(&next_row))
+pub mod utils {
+    /// Sequence fitted by its difference table, stored in Newton form so that it can be
+    /// evaluated at any index without rebuilding the table.
+    pub struct Polynomial {
+        /// First value of each row of the difference table, up to the first zero row.
+        differences: Vec<i128>,
+        sequence_len: usize,
+        degree: Option<usize>,
+    }
+
+    impl Polynomial {
+        pub fn fit(sequence: &[i128]) -> Result<Self, &'static str> {
+            if sequence.is_empty() {
+                return Err("Empty sequence");
+            }
+
+            let mut differences = Vec::new();
+            let mut row = sequence.to_vec();
+            let mut degree = None;
+            while let Some(&first) = row.first() {
+                if row.iter().all(|&value| value == 0) {
+                    degree = Some(differences.len().saturating_sub(1));
+                    break;
+                }
+                differences.push(first);
+                row = row
+                    .windows(2)
+                    .map(|w| w[1].checked_sub(w[0]).ok_or("Overflow"))
+                    .collect::<Result<_, _>>()?;
+            }
+
+            Ok(Self {
+                differences,
+                sequence_len: sequence.len(),
+                degree,
+            })
+        }
+
+        /// Number of values the polynomial was fitted to.
+        pub fn sequence_len(&self) -> usize {
+            self.sequence_len
+        }
+
+        /// Degree of the polynomial, or `None` if the sequence is too short for its
+        /// differences to reach zero.
+        pub fn degree(&self) -> Option<usize> {
+            self.degree
+        }
+
+        /// Value at index `k`, where index 0 is the first value of the sequence. Negative
+        /// indices extrapolate backwards. Returns `None` on overflow.
+        pub fn value_at(&self, k: i128) -> Option<i128> {
+            // Newton's forward formula: the sum of binomial(k, j) times the j-th difference
+            let mut binomial: i128 = 1;
+            let mut value: i128 = 0;
+            for (j, &difference) in (0_i128..).zip(&self.differences) {
+                if j > 0 {
+                    binomial = binomial.checked_mul(k.checked_sub(j - 1)?)? / j;
+                }
+                value = value.checked_add(binomial.checked_mul(difference)?)?;
+            }
+            Some(value)
+        }
+    }
 }
 
 // Test module
@@ -64,4 +126,22 @@ mod tests {
     fn test_part2() {
         assert_eq!(solve_part2(&parse_input(SAMPLE_INPUT)), 2);
     }
+
+    #[test]
+    fn polynomial_fit() {
+        let polynomial = utils::Polynomial::fit(&[1, 3, 6, 10, 15, 21]).unwrap();
+        assert_eq!(polynomial.degree(), Some(2));
+        assert_eq!(polynomial.value_at(-3), Some(1));
+        assert_eq!(polynomial.value_at(99), Some(5050));
+
+        let polynomial = utils::Polynomial::fit(&[1, 2, 4, 8]).unwrap();
+        assert_eq!(polynomial.degree(), None);
+        assert_eq!(polynomial.value_at(4), Some(15));
+
+        let polynomial = utils::Polynomial::fit(&[0, 1, 4]).unwrap();
+        assert_eq!(polynomial.value_at(i128::MAX), None);
+        let polynomial = utils::Polynomial::fit(&[3, 5, 7]).unwrap();
+        assert_eq!(polynomial.value_at(1 << 120), Some((1 << 121) + 3));
+        assert!(utils::Polynomial::fit(&[]).is_err());
+    }
 }
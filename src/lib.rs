@@ -4,6 +4,7 @@ extern crate aoc_runner;
 extern crate aoc_runner_derive;
 extern crate crypto;
 
+pub mod cycle;
 pub mod day1;
 pub mod day10;
 pub mod day11;